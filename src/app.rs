@@ -4,7 +4,10 @@ use std::sync::{Arc, Mutex};
 use crate::gamut::{Okhsva, Oklrcha, clamp_rgba, gamut_clip_preserve_chroma};
 use crate::gl_programs::{GlowProgram, ProgramKind};
 use crate::{
-    formats::{ColorFormat, format_color, parse_color},
+    formats::{
+        AlphaMode, ColorFormat, ColorStyle, FormatOptions, HueUnit, NumberStyle,
+        format_color_styled, nearest_match_distance, parse_color,
+    },
     log_startup,
 };
 use crate::{lerp, map};
@@ -464,6 +467,12 @@ pub struct App {
     colors: CurrentColors,
     format: ColorFormat,
     use_alpha: bool,
+    /// Textual style of the input color, kept in the output
+    style: ColorStyle,
+    /// Output options from the settings
+    format_options: FormatOptions,
+    /// Output options from command line flags, these override the settings
+    cli_format_options: FormatOptions,
     programs: HashMap<ProgramKind, Arc<Mutex<GlowProgram>>>,
     input_text: HashMap<u8, String>,
     first_frame: bool,
@@ -482,7 +491,10 @@ pub struct App {
 }
 
 impl App {
-    pub fn new(cc: &eframe::CreationContext<'_>, data: Arc<(Color, ColorFormat, bool)>) -> Self {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        data: Arc<(Color, ColorFormat, bool, ColorStyle, FormatOptions)>,
+    ) -> Self {
         log_startup::log("App new");
         setup_egui_config(&cc.egui_ctx);
         log_startup::log("Egui custom setup");
@@ -502,12 +514,18 @@ impl App {
 
         log_startup::log("Gl programs created");
 
-        let AppData { mode } = Self::load(cc.storage);
+        let AppData {
+            mode,
+            format_options,
+        } = Self::load(cc.storage);
 
         Self {
             colors: CurrentColors::new(mode, data.0),
             format: data.1,
             use_alpha: data.2,
+            style: data.3.clone(),
+            format_options,
+            cli_format_options: data.4,
             programs,
             input_text: Default::default(),
             first_frame: true,
@@ -531,14 +549,25 @@ impl App {
             .unwrap_or_default()
     }
 
+    fn format_color(&self, color: LinearRgba) -> String {
+        format_color_styled(
+            color,
+            self.format,
+            self.use_alpha,
+            &self.style,
+            &self.cli_format_options.or(self.format_options),
+        )
+    }
+
     fn calculate_fallbacks(&mut self) {
         let color_rgba: LinearRgba = self.colors.color_rgba();
         let prev_color_rgba: LinearRgba = self.colors.prev_color_rgba();
 
         let is_oklch = self.colors.discriminant() == CurrentColorsDiscriminants::Oklrch;
+        let format = self.format;
 
         let gamut_clip = |color: LinearRgba| -> (LinearRgba, bool) {
-            if is_oklch {
+            let (clipped, is_fallback) = if is_oklch {
                 let clipped = gamut_clip_preserve_chroma(color);
                 let is_fallback = clipped
                     .to_f32_array_no_alpha()
@@ -548,7 +577,9 @@ impl App {
                 (clipped, is_fallback)
             } else {
                 (clamp_rgba(color), false)
-            }
+            };
+            // Show what reduced-depth formats like RGB565 can actually store
+            (format.quantize(clipped), is_fallback)
         };
 
         let (color_fallback, is_cur_fallback) = gamut_clip(color_rgba);
@@ -826,6 +857,85 @@ impl App {
         });
     }
 
+    fn update_format_options(&mut self, ui: &mut egui::Ui) {
+        ui.label(RichText::new("Output").size(20.).strong());
+        ui.add_space(10.);
+
+        fn option_combo<T: Copy + PartialEq>(
+            ui: &mut Ui,
+            label: &str,
+            value: &mut Option<T>,
+            choices: impl IntoIterator<Item = (T, String)>,
+        ) {
+            ui.label(label);
+            let choices = choices
+                .into_iter()
+                .map(|(v, name)| (Some(v), name))
+                .collect::<Vec<_>>();
+            let selected_text = choices
+                .iter()
+                .find(|(v, _)| v == value)
+                .map_or("Auto", |(_, name)| name.as_str())
+                .to_owned();
+            egui::ComboBox::from_id_salt(label)
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(value, None, "Auto");
+                    for (v, name) in choices {
+                        ui.selectable_value(value, v, name);
+                    }
+                });
+            ui.end_row();
+        }
+
+        let yes_no = || [(true, "Yes".to_owned()), (false, "No".to_owned())];
+
+        let options = &mut self.format_options;
+        egui::Grid::new("format_options")
+            .num_columns(2)
+            .spacing(Vec2::new(20., 6.))
+            .show(ui, |ui| {
+                let precision_names = [
+                    "Channel 1 decimals",
+                    "Channel 2 decimals",
+                    "Channel 3 decimals",
+                    "Alpha decimals",
+                ];
+                for (name, precision) in precision_names.into_iter().zip(&mut options.precision) {
+                    option_combo(ui, name, precision, (0..=6).map(|p| (p, p.to_string())));
+                }
+                option_combo(ui, "Uppercase hex", &mut options.uppercase_hex, yes_no());
+                option_combo(ui, "Short hex", &mut options.short_hex, yes_no());
+                option_combo(
+                    ui,
+                    "Hue unit",
+                    &mut options.hue_unit,
+                    HueUnit::iter().map(|u| (u, u.to_string())),
+                );
+                option_combo(
+                    ui,
+                    "Lightness",
+                    &mut options.lightness,
+                    NumberStyle::iter().map(|s| (s, s.to_string())),
+                );
+                option_combo(
+                    ui,
+                    "Alpha",
+                    &mut options.alpha,
+                    NumberStyle::iter().map(|s| (s, s.to_string())),
+                );
+                option_combo(
+                    ui,
+                    "Write alpha",
+                    &mut options.alpha_mode,
+                    AlphaMode::iter().map(|m| (m, m.to_string())),
+                );
+            });
+
+        ui.add_space(5.);
+        ui.label("Auto keeps the style of the input color. Command line flags override these.");
+    }
+
     fn update_color_edit(
         &mut self,
         ui: &mut egui::Ui,
@@ -833,31 +943,43 @@ impl App {
         fallback: LinearRgba,
         id: u8,
     ) -> Id {
+        let mut error = None;
         let mut text = if let Some(text) = self.input_text.remove(&id) {
-            if let Some((c, use_alpha)) = parse_color(&text, self.format) {
-                self.use_alpha = use_alpha;
-                self.colors.assign(c, prev);
-            } else {
-                ui.style_mut().visuals.selection.stroke =
-                    egui::Stroke::new(2.0, egui::Color32::from_hex("#ce3c47").unwrap());
+            match parse_color(&text, self.format) {
+                Ok((c, use_alpha)) => {
+                    self.use_alpha = use_alpha;
+                    self.style = ColorStyle::detect(&text, self.format);
+                    self.colors.assign(c, prev);
+                }
+                Err(e) => {
+                    ui.style_mut().visuals.selection.stroke =
+                        egui::Stroke::new(2.0, egui::Color32::from_hex("#ce3c47").unwrap());
+                    error = Some(e);
+                }
             }
 
             text
         } else {
-            format_color(fallback, self.format, self.use_alpha)
+            self.format_color(fallback)
         };
 
-        let output = egui::TextEdit::singleline(&mut text)
+        let mut response = egui::TextEdit::singleline(&mut text)
             .margin(6.0)
             .desired_width(f32::INFINITY)
-            .show(ui);
-        self.text_inputs.insert(output.response.id);
+            .show(ui)
+            .response
+            .response;
+        self.text_inputs.insert(response.id);
+
+        if let Some(error) = error {
+            response = response.on_hover_text(error.to_string());
+        }
 
-        if output.response.has_focus() {
+        if response.has_focus() {
             self.input_text.insert(id, text.clone());
         }
 
-        output.response.id
+        response.id
     }
 
     fn update_color_previews(&mut self, builder: StripBuilder) {
@@ -880,23 +1002,36 @@ impl App {
                         .sizes(Size::remainder(), 2)
                         .clip(true)
                         .horizontal(|mut strip| {
-                            let color_label = |text: &str, fallback: bool| {
+                            let format = self.format;
+                            let color_label = |text: &str, fallback: bool, color: LinearRgba| {
+                                let nearest = nearest_match_distance(color, format)
+                                    .map(|d| format!(" (nearest, deltaEOK {d:.3})"));
                                 egui::Label::new(format!(
-                                    "{text}{}",
-                                    if fallback { " (fallback)" } else { "" }
+                                    "{text}{}{}",
+                                    if fallback { " (fallback)" } else { "" },
+                                    nearest.unwrap_or_default()
                                 ))
                                 .wrap_mode(egui::TextWrapMode::Truncate)
                             };
 
                             strip.cell(|ui| {
                                 self.update_color_edit(ui, true, self.fallbacks.prev, 0);
-                                color_label("Previous Color", self.fallbacks.is_prev_fallback)
-                                    .ui(ui);
+                                color_label(
+                                    "Previous Color",
+                                    self.fallbacks.is_prev_fallback,
+                                    self.fallbacks.prev,
+                                )
+                                .ui(ui);
                             });
 
                             strip.cell(|ui| {
                                 self.update_color_edit(ui, false, self.fallbacks.cur, 1);
-                                color_label("New Color", self.fallbacks.is_cur_fallback).ui(ui);
+                                color_label(
+                                    "New Color",
+                                    self.fallbacks.is_cur_fallback,
+                                    self.fallbacks.cur,
+                                )
+                                .ui(ui);
                             });
                         });
                 });
@@ -925,8 +1060,14 @@ impl App {
                     style.visuals.widgets.inactive.bg_stroke = egui::Stroke::NONE;
                 }))
                 .show_ui(ui, |ui| {
-                    for format in ColorFormat::iter() {
-                        ui.selectable_value(&mut self.format, format, format.to_string());
+                    for format in ColorFormat::all() {
+                        // The captured style only applies to the format it was captured from
+                        if ui
+                            .selectable_value(&mut self.format, format, format.to_string())
+                            .changed()
+                        {
+                            self.style = ColorStyle::default();
+                        }
                     }
                 }).response.id;
 
@@ -1010,6 +1151,9 @@ impl App {
                     ui.label("Hold Ctrl (or Cmd on macOS) to force switching focus when the focused input would consume that key.");
                     ui.add_space(5.);
                     ui.label("Hold Shift to change values in larger steps.");
+
+                    ui.add_space(20.);
+                    self.update_format_options(ui);
                 });
 
             if !show_settings {
@@ -1074,21 +1218,20 @@ impl App {
             if cfg!(target_arch = "wasm32") {
                 let copy = self.hotkey(ui, Key::C);
                 if response.clicked() || copy {
-                    ui.ctx().copy_text(format_color(
-                        self.fallbacks.cur,
-                        self.format,
-                        self.use_alpha,
-                    ));
+                    ui.ctx().copy_text(self.format_color(self.fallbacks.cur));
                     self.copied_notice = Some(Instant::now());
                 }
             } else {
                 let done = self.hotkey(ui, Key::D);
                 let quit = self.hotkey(ui, Key::Q);
                 if response.clicked() || done {
-                    println!(
-                        "{}",
-                        format_color(self.fallbacks.cur, self.format, self.use_alpha)
-                    );
+                    println!("{}", self.format_color(self.fallbacks.cur));
+                    if let Some(d) = nearest_match_distance(self.fallbacks.cur, self.format) {
+                        eprintln!(
+                            "No exact match in {}, wrote the nearest (deltaEOK {d:.3})",
+                            self.format
+                        );
+                    }
                     ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close)
                 } else if quit {
                     ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
@@ -1134,12 +1277,14 @@ impl App {
 #[serde(default)]
 struct AppData {
     mode: CurrentColorsDiscriminants,
+    format_options: FormatOptions,
 }
 
 impl Default for AppData {
     fn default() -> Self {
         Self {
             mode: CurrentColorsDiscriminants::Oklrch,
+            format_options: FormatOptions::default(),
         }
     }
 }
@@ -1375,6 +1520,7 @@ impl eframe::App for App {
             "app_data",
             &AppData {
                 mode: self.colors.discriminant(),
+                format_options: self.format_options,
             },
         );
     }
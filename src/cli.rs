@@ -1,17 +1,94 @@
-use clap::Parser;
+use clap::{
+    Parser,
+    builder::{PossibleValuesParser, TypedValueParser},
+};
 
-use crate::formats::ColorFormat;
+use crate::formats::{AlphaMode, ColorFormat, FormatOptions, HueUnit, NumberStyle};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
 pub struct Cli {
     /// The color format to use (default: try to auto detect)
     ///
-    /// Note that the auto detection can't distinguish some raw formats from each other.
-    /// Only raw_rgb and raw_rgb_float are attempted.
-    #[arg(short, long)]
+    /// Note that the auto detection can only guess between raw formats by their value ranges.
+    /// Use --format-hint to prefer some of them.
+    ///
+    /// Hex colors are read as RGBA and hex literals as ARGB. Other byte orders, like hex_argb or
+    /// colorref, have to be picked with this.
+    ///
+    /// User defined formats are read from `oklch-color-picker/formats.conf` in the config
+    /// directory, or from the file in $OKLCH_COLOR_PICKER_FORMATS.
+    #[arg(short, long, value_parser = format_parser())]
     pub format: Option<ColorFormat>,
 
+    /// Comma separated formats to prefer, in order, when the auto detection finds several
+    /// likely ones
+    #[arg(long, value_delimiter = ',', value_parser = format_parser())]
+    pub format_hint: Vec<ColorFormat>,
+
     /// Color to pre-select (default: get a random color)
     pub color: Option<String>,
+
+    /// Output decimals, either one value for all channels or four comma separated values
+    /// for the three channels and alpha
+    #[arg(long, value_parser = precision_parser)]
+    pub precision: Option<[u8; 4]>,
+
+    /// Write hex digits in uppercase (default: same as input)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub uppercase_hex: Option<bool>,
+
+    /// Shorten hex colors like #aabbcc to #abc when possible, and write X11 `rgb:` colors with
+    /// 2 instead of 4 digits per channel (default: same as input)
+    #[arg(long, num_args = 0..=1, require_equals = true, default_missing_value = "true")]
+    pub short_hex: Option<bool>,
+
+    /// Unit of hues in CSS formats (default: same as input, or a plain number)
+    #[arg(long)]
+    pub hue_unit: Option<HueUnit>,
+
+    /// Write lightness as a number or a percentage (default: same as input)
+    #[arg(long)]
+    pub lightness: Option<NumberStyle>,
+
+    /// Write alpha as a number or a percentage (default: same as input)
+    #[arg(long)]
+    pub alpha: Option<NumberStyle>,
+
+    /// Always or never write alpha (default: when not opaque or the input had it)
+    #[arg(long)]
+    pub alpha_mode: Option<AlphaMode>,
+}
+
+impl Cli {
+    pub fn format_options(&self) -> FormatOptions {
+        FormatOptions {
+            precision: self.precision.map_or([None; 4], |p| p.map(Some)),
+            uppercase_hex: self.uppercase_hex,
+            short_hex: self.short_hex,
+            hue_unit: self.hue_unit,
+            lightness: self.lightness,
+            alpha: self.alpha,
+            alpha_mode: self.alpha_mode,
+        }
+    }
+}
+
+/// Accepts the built-in formats and the user templates, which have to be loaded before parsing
+fn format_parser() -> impl TypedValueParser<Value = ColorFormat> {
+    PossibleValuesParser::new(ColorFormat::all().map(ColorFormat::name))
+        .map(|name| ColorFormat::from_name(&name).unwrap())
+}
+
+/// One value for all channels or four comma separated values
+fn precision_parser(s: &str) -> Result<[u8; 4], String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<u8>().map_err(|e| format!("`{v}`: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    match values[..] {
+        [p] => Ok([p; 4]),
+        [c1, c2, c3, alpha] => Ok([c1, c2, c3, alpha]),
+        _ => Err(format!("expected 1 or 4 values, got {}", values.len())),
+    }
 }
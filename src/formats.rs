@@ -1,59 +1,479 @@
-use std::sync::LazyLock;
+use std::{fmt, ops::Range, sync::LazyLock};
 
-use bevy_color::{Color, ColorToComponents, ColorToPacked, Hsla, LinearRgba, Oklcha, Srgba};
+use bevy_color::{
+    Color, ColorToComponents, ColorToPacked, Hsla, Hwba, LinearRgba, Luminance, Oklaba, Oklcha,
+    Srgba,
+};
 use clap::ValueEnum;
 use lexical_parse_float::FromLexicalWithOptions;
+use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
 use winnow::{
     ModalResult, Parser,
-    ascii::{digit0, digit1, space0, space1},
-    combinator::{alt, delimited, opt, separated, terminated},
-    error::ParserError,
+    ascii::{Caseless, digit0, digit1, space0, space1},
+    combinator::{alt, cut_err, delimited, fail, opt, preceded, repeat, separated, terminated},
+    error::{ContextError, ErrMode, ParserError, StrContext, StrContextValue},
+    token::{one_of, take_till, take_while},
 };
 
-#[derive(ValueEnum, Default, Clone, Copy, strum::Display, strum::EnumIter, PartialEq, Eq)]
-#[clap(rename_all = "snake_case")]
+use crate::{
+    gamut::{Okhsla, Okhsva, nearest_xterm_color, xterm_color},
+    mix::{HueInterpolation, MixSpace, mix},
+    named_colors::{nearest_named_color, parse_named_color},
+    spaces::{CieLaba, CieLcha, Cmyka, CssColorSpace, Xyya},
+    templates::templates,
+};
+
+#[derive(Default, Debug, Clone, Copy, strum::IntoStaticStr, strum::EnumIter, PartialEq, Eq)]
 #[strum(serialize_all = "snake_case")]
 pub enum ColorFormat {
     #[default]
     Hex,
+    HexArgb,
+    HexAbgr,
     Oklch,
+    Oklab,
     Rgb,
     Hsl,
+    Hwb,
+    Lab,
+    Lch,
     RgbLegacy,
     HslLegacy,
     HexLiteral,
+    HexLiteralRgba,
+    HexLiteralAbgr,
+    Colorref,
     RawRgb,
+    RawRgb10,
+    RawRgb16,
+    PackedArgb,
+    PackedRgba,
+    Rgb565,
+    Bgr565,
+    Rgb332,
+    Argb4444,
+    Rgb444,
     RawRgbFloat,
     RawRgbLinear,
     RawOklch,
+    ColorSrgb,
+    ColorSrgbLinear,
+    ColorDisplayP3,
+    ColorA98Rgb,
+    ColorProphotoRgb,
+    ColorRec2020,
+    ColorXyzD50,
+    ColorXyzD65,
+    Glsl,
+    GlslLinear,
+    Wgsl,
+    WgslLinear,
+    Hlsl,
+    HlslLinear,
+    BevySrgb,
+    BevyLinearRgb,
+    BevyOklch,
+    BevyHex,
+    UnityColor,
+    UnityColor32,
+    GodotColor,
+    GodotColor8,
+    GodotHex,
+    SwiftuiColor,
+    UikitColor,
+    ComposeColor,
+    ColorArgbHex,
+    FlutterArgb,
+    FlutterRgbo,
+    X11Rgb,
+    X11Rgbi,
+    AnsiTruecolor,
+    Ansi256,
+    Cmyk,
+    DeviceCmyk,
+    RawCmyk,
+    Okhsv,
+    Okhsl,
+    RawOkhsv,
+    RawOkhsl,
+    RawXyz,
+    RawXyzD50,
+    RawXyy,
+    LatexHtml,
+    LatexRgb,
+    LatexRgb8,
+    TypstHex,
+    TypstOklch,
+    TypstLuma,
+    Named,
+    /// A user defined format, by its index in [`templates()`]
+    #[strum(disabled)]
+    Template(usize),
+}
+
+impl fmt::Display for ColorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 impl ColorFormat {
+    /// The built-in formats followed by the user templates
+    pub fn all() -> impl Iterator<Item = ColorFormat> {
+        ColorFormat::iter().chain((0..templates().len()).map(ColorFormat::Template))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorFormat::Template(i) => &templates()[i].name,
+            _ => self.into(),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ColorFormat::all().find(|f| f.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Raw XYZ and xyY values and packed integers look like any other numbers, and
+    /// reduced-depth or reordered hex looks like any other hex, so they have to be asked for.
+    /// Bare xterm-256 indices are ruled out by [`Self::confidence`] instead.
     fn is_auto_detectable(&self) -> bool {
         use ColorFormat as F;
         matches!(
             *self,
             F::Hex
                 | F::Oklch
+                | F::Oklab
                 | F::Rgb
                 | F::Hsl
+                | F::Hwb
+                | F::Lab
+                | F::Lch
                 | F::RgbLegacy
                 | F::HslLegacy
                 | F::HexLiteral
                 | F::RawRgb
+                | F::RawRgb10
+                | F::RawRgb16
+                | F::RawRgbFloat
+                | F::RawRgbLinear
+                | F::RawOklch
+                | F::ColorSrgb
+                | F::ColorSrgbLinear
+                | F::ColorDisplayP3
+                | F::ColorA98Rgb
+                | F::ColorProphotoRgb
+                | F::ColorRec2020
+                | F::ColorXyzD50
+                | F::ColorXyzD65
+                | F::Glsl
+                | F::GlslLinear
+                | F::Wgsl
+                | F::WgslLinear
+                | F::Hlsl
+                | F::HlslLinear
+                | F::BevySrgb
+                | F::BevyLinearRgb
+                | F::BevyOklch
+                | F::BevyHex
+                | F::UnityColor
+                | F::UnityColor32
+                | F::GodotColor
+                | F::GodotColor8
+                | F::GodotHex
+                | F::SwiftuiColor
+                | F::UikitColor
+                | F::ComposeColor
+                | F::ColorArgbHex
+                | F::FlutterArgb
+                | F::FlutterRgbo
+                | F::X11Rgb
+                | F::X11Rgbi
+                | F::AnsiTruecolor
+                | F::Ansi256
+                | F::Cmyk
+                | F::DeviceCmyk
+                | F::RawCmyk
+                | F::Okhsv
+                | F::Okhsl
+                | F::RawOkhsv
+                | F::RawOkhsl
+                | F::LatexHtml
+                | F::LatexRgb
+                | F::LatexRgb8
+                | F::TypstHex
+                | F::TypstOklch
+                | F::TypstLuma
+                | F::Named
+                | F::Template(_)
+        )
+    }
+
+    /// How likely it is that `s`, which parses as this format, was meant as it.
+    /// Only the raw and shader formats overlap. The raw formats are told apart by the ranges
+    /// of their values. At 0 the format is never guessed.
+    fn confidence(self, s: &str) -> f32 {
+        use ColorFormat as F;
+        // Any integer could be an xterm-256 index, only escapes and `colour196` are clear
+        if self == F::Ansi256 && s.bytes().all(|b| b.is_ascii_digit()) {
+            return 0.;
+        }
+        // A user template can look like any built-in format, which should still win
+        if matches!(self, F::Template(_)) {
+            return 0.05;
+        }
+        // Shader constants are usually linear
+        if matches!(self, F::Glsl | F::Wgsl | F::Hlsl) {
+            return 0.9;
+        }
+        if !matches!(
+            self,
+            F::RawRgb
+                | F::RawRgb10
+                | F::RawRgb16
                 | F::RawRgbFloat
+                | F::RawRgbLinear
+                | F::RawOklch
+                | F::RawCmyk
+                | F::RawOkhsv
+                | F::RawOkhsl
+        ) {
+            return 1.;
+        }
+        let parsed: Result<Vec<f32>, _> = separated(3..=5, number_parser, comma_parser).parse(s);
+        let Ok(values) = parsed else {
+            return 1.;
+        };
+
+        let unit = |v: &f32| (0. ..=1.).contains(v);
+        let rgb = &values[..3];
+        let alpha = values.get(3).is_none_or(unit);
+        match self {
+            F::RawRgb if rgb.iter().any(|v| *v > 1.) => 0.9,
+            F::RawRgb => 0.3,
+            F::RawRgb10 if rgb.iter().any(|v| *v > 255.) => 0.8,
+            F::RawRgb16 if rgb.iter().any(|v| *v > 1023.) => 0.8,
+            // Both deeper formats fit, but 10-bit is the tighter one
+            F::RawRgb16 if rgb.iter().any(|v| *v > 255.) => 0.7,
+            F::RawRgb10 | F::RawRgb16 => 0.1,
+            F::RawRgbFloat if alpha && rgb.iter().all(unit) => 0.6,
+            F::RawRgbLinear if alpha && rgb.iter().all(unit) => 0.5,
+            // Values outside of the sRGB gamut are common in linear shader code
+            F::RawRgbLinear if alpha => 0.4,
+            F::RawOklch
+                if alpha
+                    && unit(&values[0])
+                    && (0. ..=0.5).contains(&values[1])
+                    && (0. ..=360.).contains(&values[2]) =>
+            {
+                // Only a hue goes above 1
+                if values[2] > 1. { 0.9 } else { 0.3 }
+            }
+            // Four numbers are much more often RGBA
+            F::RawCmyk => 0.2,
+            // Only a hue goes above 1, and nothing tells Okhsv and Okhsl apart
+            F::RawOkhsv | F::RawOkhsl if values[0] > 1. => 0.8,
+            F::RawOkhsv | F::RawOkhsl => 0.3,
+            _ => 0.05,
+        }
+    }
+
+    /// The predefined space of a CSS `color()` function format
+    fn css_color_space(&self) -> Option<CssColorSpace> {
+        use ColorFormat as F;
+        use CssColorSpace as S;
+        Some(match *self {
+            F::ColorSrgb => S::Srgb,
+            F::ColorSrgbLinear => S::SrgbLinear,
+            F::ColorDisplayP3 => S::DisplayP3,
+            F::ColorA98Rgb => S::A98Rgb,
+            F::ColorProphotoRgb => S::ProphotoRgb,
+            F::ColorRec2020 => S::Rec2020,
+            F::ColorXyzD50 => S::XyzD50,
+            F::ColorXyzD65 => S::XyzD65,
+            _ => return None,
+        })
+    }
+
+    /// Formats written as a CSS color function, where a `color-mix()` can be used instead
+    fn is_css_function(&self) -> bool {
+        use ColorFormat as F;
+        self.css_color_space().is_some()
+            || matches!(
+                *self,
+                F::Oklch
+                    | F::Oklab
+                    | F::Rgb
+                    | F::Hsl
+                    | F::Hwb
+                    | F::Lab
+                    | F::Lch
+                    | F::RgbLegacy
+                    | F::HslLegacy
+            )
+    }
+
+    /// The names of a format written as a call like `vec3(..)` or `Color::srgba(..)`,
+    /// taking 3 and 4 components. The first names are used for output.
+    #[allow(clippy::type_complexity)]
+    fn call_names(&self) -> Option<(&'static [&'static str], &'static [&'static str])> {
+        use ColorFormat as F;
+        Some(match *self {
+            F::Glsl | F::GlslLinear => (&["vec3"], &["vec4"]),
+            F::Wgsl | F::WgslLinear => (&["vec3f", "vec3<f32>"], &["vec4f", "vec4<f32>"]),
+            F::Hlsl | F::HlslLinear => (&["float3"], &["float4"]),
+            F::BevySrgb => (&["Color::srgb"], &["Color::srgba"]),
+            F::BevyLinearRgb => (&["Color::linear_rgb"], &["Color::linear_rgba"]),
+            F::BevyOklch => (&["Color::oklch"], &["Color::oklcha"]),
+            F::UnityColor => (&["new Color"], &["new Color"]),
+            F::UnityColor32 => (&[], &["new Color32"]),
+            F::GodotColor => (&["Color"], &["Color"]),
+            F::GodotColor8 => (&["Color8"], &["Color8"]),
+            F::FlutterArgb => (&[], &["Color.fromARGB"]),
+            _ => return None,
+        })
+    }
+
+    /// The components of a call format
+    fn call_values(&self, color: LinearRgba) -> [f32; 4] {
+        use ColorFormat as F;
+        match *self {
+            F::GlslLinear | F::WgslLinear | F::HlslLinear | F::BevyLinearRgb => {
+                color.to_f32_array()
+            }
+            F::BevyOklch => Oklcha::from(color).to_f32_array(),
+            F::FlutterArgb => {
+                let mut values = Srgba::from(color).to_f32_array();
+                values.rotate_right(1);
+                values
+            }
+            _ => Srgba::from(color).to_f32_array(),
+        }
+    }
+
+    fn call_color(&self, values: [f32; 4]) -> Color {
+        use ColorFormat as F;
+        match *self {
+            F::GlslLinear | F::WgslLinear | F::HlslLinear | F::BevyLinearRgb => {
+                LinearRgba::from_f32_array(values).into()
+            }
+            F::BevyOklch => Oklcha::from_f32_array(values).into(),
+            F::FlutterArgb => {
+                let mut values = values;
+                values.rotate_left(1);
+                Srgba::from_f32_array(values).into()
+            }
+            _ => Srgba::from_f32_array(values).into(),
+        }
+    }
+
+    /// Call formats with integer components from 0 to 255
+    fn has_byte_components(&self) -> bool {
+        matches!(
+            *self,
+            ColorFormat::UnityColor32 | ColorFormat::GodotColor8 | ColorFormat::FlutterArgb
         )
     }
 
+    /// The name, argument labels, label separator and whether alpha is required, of a format
+    /// written as a call with labelled arguments like `Color(red: 1, green: 0.5, blue: 0)`
+    fn labelled_call(&self) -> Option<(&'static str, [&'static str; 4], char, bool)> {
+        use ColorFormat as F;
+        Some(match *self {
+            F::SwiftuiColor => ("Color", ["red", "green", "blue", "opacity"], ':', false),
+            F::UikitColor => ("UIColor", ["red", "green", "blue", "alpha"], ':', true),
+            F::ComposeColor => ("Color", ["red", "green", "blue", "alpha"], '=', false),
+            _ => return None,
+        })
+    }
+
+    /// The RGBA channel index of each byte in a hex format, starting from the most significant.
+    /// Without alpha, the alpha byte is left out.
+    fn hex_byte_order(&self) -> Option<[usize; 4]> {
+        use ColorFormat as F;
+        Some(match *self {
+            F::Hex | F::HexLiteralRgba => [0, 1, 2, 3],
+            F::HexArgb | F::HexLiteral => [3, 0, 1, 2],
+            // COLORREF has an unused byte where alpha would be
+            F::HexAbgr | F::HexLiteralAbgr | F::Colorref => [3, 2, 1, 0],
+            _ => return None,
+        })
+    }
+
+    /// The RGBA channel index and bit count of each channel in a reduced-depth packed format
+    /// like RGB565, starting from the most significant bits
+    fn packed_layout(&self) -> Option<&'static [(usize, u32)]> {
+        use ColorFormat as F;
+        Some(match *self {
+            F::Rgb565 => &[(0, 5), (1, 6), (2, 5)],
+            F::Bgr565 => &[(2, 5), (1, 6), (0, 5)],
+            F::Rgb332 => &[(0, 3), (1, 3), (2, 2)],
+            F::Argb4444 => &[(3, 4), (0, 4), (1, 4), (2, 4)],
+            F::Rgb444 => &[(0, 4), (1, 4), (2, 4)],
+            _ => return None,
+        })
+    }
+
+    /// Hex digits written for a packed format
+    fn packed_digits(&self) -> usize {
+        let bits: u32 = self
+            .packed_layout()
+            .unwrap_or_default()
+            .iter()
+            .map(|(_, b)| b)
+            .sum();
+        bits.div_ceil(4) as usize
+    }
+
+    /// The color as it is stored in this format. Reduced-depth formats like RGB565 round
+    /// the channels to a few bits, other formats keep the color as is.
+    // Not really dead but my lib system messes with compilation
+    #[allow(dead_code)]
+    pub fn quantize(&self, color: LinearRgba) -> LinearRgba {
+        match self.packed_layout() {
+            Some(layout) => unpack_hex(pack_hex(color.into(), layout), layout).into(),
+            None => color,
+        }
+    }
+
+    fn lightness_index(&self) -> Option<usize> {
+        use ColorFormat as F;
+        match *self {
+            F::Oklch | F::Oklab | F::Lab | F::Lch => Some(0),
+            F::Hsl | F::HslLegacy | F::Okhsl => Some(2),
+            F::TypstLuma => Some(0),
+            _ => None,
+        }
+    }
+
     // Not really dead but my lib system messes with compilation
     #[allow(dead_code)]
     pub fn needs_explicit_alpha(&self) -> bool {
         use ColorFormat as F;
         matches!(
             *self,
-            F::HexLiteral | F::RawRgb | F::RawRgbFloat | F::RawRgbLinear | F::RawOklch
-        )
+            F::HexLiteral
+                | F::HexLiteralRgba
+                | F::HexLiteralAbgr
+                | F::RawRgb
+                | F::RawRgb10
+                | F::RawRgb16
+                | F::PackedArgb
+                | F::RawRgbFloat
+                | F::RawRgbLinear
+                | F::RawOklch
+                | F::RawCmyk
+                | F::RawOkhsv
+                | F::RawOkhsl
+                | F::RawXyz
+                | F::RawXyzD50
+                | F::RawXyy
+                | F::SwiftuiColor
+                | F::ComposeColor
+        ) || self
+            .call_names()
+            .is_some_and(|(rgb, rgba)| !rgb.is_empty() && !rgba.is_empty())
     }
 }
 
@@ -68,204 +488,1572 @@ fn num(v: f32, decimals: i32) -> f32 {
     }
 }
 
-fn css_alpha(alpha: f32) -> String {
-    if alpha < 1. {
-        format!(" / {}%", num(alpha * 100., 1))
-    } else {
-        String::new()
+/// Like `num`, but keeps the sign for components that can be negative.
+pub fn signed_num(v: f32, decimals: i32) -> f32 {
+    let factor = 10.0f32.powi(decimals);
+    let n = (v * factor).round() / factor;
+
+    if n == 0. { 0. } else { n }
+}
+
+/// How a CSS channel or alpha is written
+#[derive(
+    ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum::Display,
+    strum::EnumIter,
+    Serialize,
+    Deserialize,
+)]
+#[clap(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum NumberStyle {
+    Number,
+    Percent,
+}
+
+/// Unit of a CSS hue. `Number` is a plain number of degrees.
+#[derive(
+    ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum::Display,
+    strum::EnumIter,
+    Serialize,
+    Deserialize,
+)]
+#[clap(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum HueUnit {
+    Number,
+    Deg,
+    Rad,
+    Grad,
+    Turn,
+}
+
+/// Whether to write alpha. Without a mode, alpha is written when it's needed
+/// or when the input had it.
+#[derive(
+    ValueEnum,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    strum::Display,
+    strum::EnumIter,
+    Serialize,
+    Deserialize,
+)]
+#[clap(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum AlphaMode {
+    Always,
+    Never,
+}
+
+impl AlphaMode {
+    fn write_alpha(mode: Option<Self>, auto: bool) -> bool {
+        match mode {
+            None => auto,
+            Some(Self::Always) => true,
+            Some(Self::Never) => false,
+        }
     }
 }
 
-fn css_legacy_alpha(alpha: f32) -> String {
-    if alpha < 1. {
-        format!(", {}%", num(alpha * 100., 1))
-    } else {
-        String::new()
+/// User preferences for `format_color_styled`.
+/// `None` keeps the style of the input, or the default of the format.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatOptions {
+    /// Decimals of the three channels and alpha
+    pub precision: [Option<u8>; 4],
+    pub uppercase_hex: Option<bool>,
+    /// Use `#abc` instead of `#aabbcc` when possible
+    pub short_hex: Option<bool>,
+    pub hue_unit: Option<HueUnit>,
+    pub lightness: Option<NumberStyle>,
+    pub alpha: Option<NumberStyle>,
+    pub alpha_mode: Option<AlphaMode>,
+}
+
+impl FormatOptions {
+    /// Options of `self` with unset ones taken from `other`
+    // Not really dead but my lib system messes with compilation
+    #[allow(dead_code)]
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            precision: std::array::from_fn(|i| self.precision[i].or(other.precision[i])),
+            uppercase_hex: self.uppercase_hex.or(other.uppercase_hex),
+            short_hex: self.short_hex.or(other.short_hex),
+            hue_unit: self.hue_unit.or(other.hue_unit),
+            lightness: self.lightness.or(other.lightness),
+            alpha: self.alpha.or(other.alpha),
+            alpha_mode: self.alpha_mode.or(other.alpha_mode),
+        }
+    }
+
+    fn override_style(&self, style: &ColorStyle, format: ColorFormat) -> ColorStyle {
+        let mut style = style.clone();
+        style.uppercase_hex = self.uppercase_hex.or(style.uppercase_hex);
+        style.short_hex = self.short_hex.or(style.short_hex);
+        style.hue_unit = self.hue_unit.or(style.hue_unit);
+        style.alpha = self.alpha.or(style.alpha);
+        if let Some(i) = format.lightness_index() {
+            style.channels[i] = self.lightness.or(style.channels[i]);
+        }
+        style
     }
 }
 
-fn raw_alpha(alpha: f32, use_alpha: bool) -> String {
-    if use_alpha {
-        format!(", {:?}", num(alpha, 3))
-    } else {
-        String::new()
+/// Textual details of a parsed color, so that the edited color can be written the same way.
+/// `None` means the default style of the format.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorStyle {
+    /// Function name as written, e.g. `RGBA`
+    pub name: Option<String>,
+    pub uppercase_hex: Option<bool>,
+    pub short_hex: Option<bool>,
+    /// Whitespace after `(` and before `)`
+    pub padding: Option<(String, String)>,
+    /// Text between channels, e.g. `, ` or ` `
+    pub separator: Option<String>,
+    /// Text before alpha, e.g. ` / ` or `,`
+    pub alpha_separator: Option<String>,
+    pub channels: [Option<NumberStyle>; 3],
+    pub hue_unit: Option<HueUnit>,
+    pub alpha: Option<NumberStyle>,
+}
+
+impl ColorStyle {
+    /// Captures the style of `s`, which should be a valid color in `format`.
+    /// Parts that can't be captured, like `calc()` channels, keep the default style.
+    // Not really dead but my lib system messes with compilation
+    #[allow(dead_code)]
+    pub fn detect(s: &str, format: ColorFormat) -> Self {
+        use ColorFormat as F;
+        let s = s.trim();
+        let mut style = Self::default();
+        match format {
+            F::Hex
+            | F::HexArgb
+            | F::HexAbgr
+            | F::HexLiteral
+            | F::HexLiteralRgba
+            | F::HexLiteralAbgr
+            | F::Colorref
+            | F::Rgb565
+            | F::Bgr565
+            | F::Rgb332
+            | F::Argb4444
+            | F::Rgb444 => {
+                let digits = s
+                    .strip_prefix('#')
+                    .or_else(|| s.strip_prefix("0x"))
+                    .unwrap_or(s);
+                style.uppercase_hex = if digits.chars().any(|c| c.is_ascii_uppercase()) {
+                    Some(true)
+                } else if digits.chars().any(|c| c.is_ascii_lowercase()) {
+                    Some(false)
+                } else {
+                    None
+                };
+                if matches!(format, F::Hex | F::HexArgb | F::HexAbgr) {
+                    style.short_hex = Some(matches!(digits.len(), 3 | 4));
+                }
+            }
+            F::RawRgb
+            | F::RawRgb10
+            | F::RawRgb16
+            | F::RawRgbFloat
+            | F::RawRgbLinear
+            | F::RawOklch
+            | F::Glsl
+            | F::GlslLinear
+            | F::Wgsl
+            | F::WgslLinear
+            | F::Hlsl
+            | F::HlslLinear
+            | F::BevySrgb
+            | F::BevyLinearRgb
+            | F::BevyOklch
+            | F::UnityColor
+            | F::UnityColor32
+            | F::GodotColor
+            | F::GodotColor8
+            | F::SwiftuiColor
+            | F::UikitColor
+            | F::ComposeColor
+            | F::FlutterArgb
+            | F::FlutterRgbo => {
+                if let Some(i) = s.find(',') {
+                    let start = s[..i].trim_end().len();
+                    let end = s.len() - s[i + 1..].trim_start().len();
+                    style.separator = Some(s[start..end].to_string());
+                }
+            }
+            F::BevyHex | F::GodotHex | F::TypstHex => {
+                if let Some((_, hex)) = s.split_once('"') {
+                    return Self::detect(hex.trim_end_matches([')', '"', ' ']), F::Hex);
+                }
+            }
+            F::X11Rgb => {
+                let digits = s.get(4..).unwrap_or_default();
+                style.uppercase_hex = if digits.chars().any(|c| c.is_ascii_uppercase()) {
+                    Some(true)
+                } else if digits.chars().any(|c| c.is_ascii_lowercase()) {
+                    Some(false)
+                } else {
+                    None
+                };
+                style.short_hex = digits.split('/').next().map(|d| d.len() <= 2);
+            }
+            F::AnsiTruecolor | F::Ansi256 => {
+                // Keep the escape and the foreground or background code as written
+                let values = if format == F::Ansi256 { 1 } else { 3 };
+                let prefix = s
+                    .trim_end_matches('m')
+                    .rsplitn(values + 1, |c: char| c == ';' || c.is_ascii_alphabetic())
+                    .nth(values);
+                if let Some(prefix) = prefix {
+                    let len = prefix.len() + 1;
+                    style.name = Some(s[..len].to_string());
+                }
+            }
+            F::ColorArgbHex => {
+                if let Some(i) = s.find("0x") {
+                    return Self::detect(s[i..].trim_end_matches([')', ' ']), F::HexLiteral);
+                }
+            }
+            F::LatexHtml | F::LatexRgb | F::LatexRgb8 => {
+                // Keep the command and the bracket around the model, like `\definecolor{name}{`
+                if let Some(i) = s.rfind(['[', '{'])
+                    && let Some(model) = s[..i].rfind(['[', '{'])
+                {
+                    style.name = Some(s[..model + 1].to_string());
+                    let values = &s[i + 1..];
+                    if format == F::LatexHtml {
+                        return Self {
+                            name: style.name,
+                            ..Self::detect(&format!("#{}", values.trim_end_matches('}')), F::Hex)
+                        };
+                    }
+                    if let Some(i) = values.find(',') {
+                        let start = values[..i].trim_end().len();
+                        let end = values.len() - values[i + 1..].trim_start().len();
+                        style.separator = Some(values[start..end].to_string());
+                    }
+                }
+            }
+            F::PackedArgb | F::PackedRgba | F::Named | F::Template(_) => {}
+            _ => {
+                let Ok((name, left_pad, args)) = css_function_parts_parser.parse(s) else {
+                    return style;
+                };
+                style.name = Some(name.to_string());
+                let right_pad = args.last().map_or("", |(_, sep)| *sep);
+                style.padding = Some((left_pad.to_string(), right_pad.to_string()));
+
+                // Channels of relative colors are mostly keywords
+                if args.first().is_some_and(|(arg, _)| *arg == "from") {
+                    return style;
+                }
+                let args = if format.css_color_space().is_some() {
+                    &args[1.min(args.len())..]
+                } else {
+                    &args[..]
+                };
+                let hue_index = match format {
+                    F::Hsl | F::HslLegacy | F::Hwb | F::Okhsv | F::Okhsl => Some(0),
+                    F::Oklch | F::Lch | F::TypstOklch => Some(2),
+                    _ => None,
+                };
+                // CMYK channels all share the style of the first one
+                let channels = match format {
+                    F::Cmyk | F::DeviceCmyk => 4,
+                    F::TypstLuma => 1,
+                    _ => 3,
+                };
+
+                for (i, (arg, _)) in args.iter().take(3).enumerate() {
+                    if Some(i) == hue_index {
+                        style.hue_unit = hue_unit_parser.parse(arg).ok();
+                    } else {
+                        style.channels[i] = number_style_parser.parse(arg).ok();
+                    }
+                }
+                if let Some((_, sep)) = args.first() {
+                    style.separator = Some(sep.to_string());
+                }
+                if let [.., (_, alpha_sep), (alpha, _)] = args
+                    && args.len() == channels + 1
+                {
+                    style.alpha_separator = Some(alpha_sep.to_string());
+                    style.alpha = number_style_parser.parse(alpha).ok();
+                }
+            }
+        }
+        style
+    }
+}
+
+/// Splits a CSS function into its name, left padding and arguments with the text after them
+#[allow(clippy::type_complexity)]
+fn css_function_parts_parser<'a>(
+    input: &mut &'a str,
+) -> ModalResult<(&'a str, &'a str, Vec<(&'a str, &'a str)>)> {
+    (
+        take_while(1.., |c: char| c.is_ascii_alphanumeric() || c == '-'),
+        preceded("(", space0),
+        terminated(
+            repeat(
+                1..,
+                (
+                    alt((
+                        (
+                            take_while(1.., |c: char| c.is_ascii_alphanumeric() || c == '-'),
+                            css_parens_parser,
+                        )
+                            .take(),
+                        take_till(1.., |c: char| {
+                            c.is_whitespace() || matches!(c, '(' | ')' | ',' | '/')
+                        }),
+                    )),
+                    take_while(0.., |c: char| c.is_whitespace() || matches!(c, ',' | '/')),
+                ),
+            ),
+            ")",
+        ),
+    )
+        .parse_next(input)
+}
+
+fn number_style_parser(input: &mut &str) -> ModalResult<NumberStyle> {
+    terminated(number_parser, opt("%"))
+        .take()
+        .map(|s: &str| {
+            if s.ends_with('%') {
+                NumberStyle::Percent
+            } else {
+                NumberStyle::Number
+            }
+        })
+        .parse_next(input)
+}
+
+fn hue_unit_parser(input: &mut &str) -> ModalResult<HueUnit> {
+    preceded(
+        number_parser,
+        opt(alt((
+            "deg".value(HueUnit::Deg),
+            "rad".value(HueUnit::Rad),
+            "grad".value(HueUnit::Grad),
+            "turn".value(HueUnit::Turn),
+        ))),
+    )
+    .map(|unit| unit.unwrap_or(HueUnit::Number))
+    .parse_next(input)
+}
+
+/// Writes a number, or a percentage where 100% is `percent_max`.
+/// `number_decimals` is the default precision of numbers, percentages use 1.
+fn css_channel(
+    value: f32,
+    percent_max: f32,
+    number_decimals: i32,
+    style: NumberStyle,
+    precision: Option<u8>,
+) -> String {
+    let decimals = |default: i32| precision.map_or(default, i32::from);
+    match style {
+        NumberStyle::Number => signed_num(value, decimals(number_decimals)).to_string(),
+        NumberStyle::Percent => format!("{}%", signed_num(value / percent_max * 100., decimals(1))),
+    }
+}
+
+fn css_hue(hue: f32, unit: HueUnit, precision: Option<u8>) -> String {
+    let decimals = |default: i32| precision.map_or(default, i32::from);
+    match unit {
+        HueUnit::Number => num(hue, decimals(1)).to_string(),
+        HueUnit::Deg => format!("{}deg", num(hue, decimals(1))),
+        HueUnit::Rad => format!("{}rad", num(hue.to_radians(), decimals(4))),
+        HueUnit::Grad => format!("{}grad", num(hue / 0.9, decimals(1))),
+        HueUnit::Turn => format!("{}turn", num(hue / 360., decimals(4))),
     }
 }
 
-fn raw_alpha_u8(alpha: u8, use_alpha: bool) -> String {
-    if use_alpha {
-        format!(", {}", alpha)
+/// Writes a CSS function with already formatted arguments
+fn css_function(
+    style: &ColorStyle,
+    name: &str,
+    legacy: bool,
+    args: &[String],
+    alpha: Option<String>,
+) -> String {
+    let name = style.name.as_deref().unwrap_or(name);
+    let (left_pad, right_pad) = style
+        .padding
+        .as_ref()
+        .map_or(("", ""), |(l, r)| (l.as_str(), r.as_str()));
+    let separator = style
+        .separator
+        .as_deref()
+        .unwrap_or(if legacy { ", " } else { " " });
+
+    let alpha = alpha.map_or(String::new(), |alpha| {
+        let alpha_separator =
+            style
+                .alpha_separator
+                .as_deref()
+                .unwrap_or(if legacy { ", " } else { " / " });
+        format!("{alpha_separator}{alpha}")
+    });
+
+    format!(
+        "{name}({left_pad}{}{alpha}{right_pad})",
+        args.join(separator)
+    )
+}
+
+/// The name and values of an Okhsv or Okhsl format
+fn okhs_values(format: ColorFormat, color: LinearRgba) -> (&'static str, [f32; 4]) {
+    let oklab = Oklaba::from(color);
+    if matches!(format, ColorFormat::Okhsv | ColorFormat::RawOkhsv) {
+        let c = Okhsva::from(oklab);
+        ("okhsv", [c.hue, c.saturation, c.value, c.alpha])
     } else {
-        String::new()
+        let c = Okhsla::from(oklab);
+        ("okhsl", [c.hue, c.saturation, c.lightness, c.alpha])
     }
 }
 
+fn raw_components(style: &ColorStyle, values: &[String]) -> String {
+    values.join(style.separator.as_deref().unwrap_or(", "))
+}
+
 #[allow(unused)]
 pub fn format_color(fallback: LinearRgba, format: ColorFormat, use_alpha: bool) -> String {
+    format_color_styled(
+        fallback,
+        format,
+        use_alpha,
+        &ColorStyle::default(),
+        &FormatOptions::default(),
+    )
+}
+
+/// The deltaEOK distance between `color` and what `format` writes for it, when the format
+/// picks the nearest of a fixed set of colors and there is no exact match
+// Not really dead but my lib system messes with compilation
+#[allow(dead_code)]
+pub fn nearest_match_distance(color: LinearRgba, format: ColorFormat) -> Option<f32> {
+    let distance = match format {
+        ColorFormat::Named => nearest_named_color(color).1,
+        ColorFormat::Ansi256 => nearest_xterm_color(color).1,
+        _ => return None,
+    };
+    (distance > 0.).then_some(distance)
+}
+
+/// Like `format_color`, but reproduces the captured style of the original input.
+/// Options that are set take precedence over the style.
+pub fn format_color_styled(
+    fallback: LinearRgba,
+    format: ColorFormat,
+    use_alpha: bool,
+    style: &ColorStyle,
+    options: &FormatOptions,
+) -> String {
+    use NumberStyle::{Number, Percent};
+    let input_has_alpha = style.alpha.is_some();
+    let style = &options.override_style(style, format);
+    let precision = options.precision;
+    let write_alpha = |auto: bool| AlphaMode::write_alpha(options.alpha_mode, auto);
+
+    let channel = |i: usize, value: f32, percent_max: f32, decimals: i32, default| {
+        let style = style.channels[i].unwrap_or(default);
+        css_channel(value, percent_max, decimals, style, precision[i])
+    };
+    let hue =
+        |i: usize, hue: f32| css_hue(hue, style.hue_unit.unwrap_or(HueUnit::Number), precision[i]);
+    let css_alpha = |alpha: f32| {
+        write_alpha(alpha < 1. || input_has_alpha).then(|| {
+            let style = style.alpha.unwrap_or(Percent);
+            css_channel(alpha, 1., 3, style, precision[3])
+        })
+    };
+    let raw = |v: f32, i: usize, decimals: i32| {
+        format!("{:?}", num(v, precision[i].map_or(decimals, i32::from)))
+    };
+
     match format {
-        ColorFormat::Hex => {
-            let arr = Srgba::from(fallback).to_u8_array();
+        ColorFormat::Hex | ColorFormat::HexArgb | ColorFormat::HexAbgr => {
+            let mut arr = Srgba::from(fallback).to_u8_array();
+            if !write_alpha(arr[3] != 255) {
+                arr[3] = 255;
+            }
             let short = arr.map(|c| (c / 17, c % 17));
-            let is_short = short.iter().all(|(_, rem)| *rem == 0);
-
-            let [r, g, b, a] = if is_short { short.map(|(d, _)| d) } else { arr };
+            let is_short = style.short_hex != Some(false) && short.iter().all(|(_, rem)| *rem == 0);
 
-            match (is_short, arr[3]) {
-                (true, 255) => format!("#{:x}{:x}{:x}", r, g, b),
-                (true, _) => format!("#{:x}{:x}{:x}{:x}", r, g, b, a),
-                (false, 255) => format!("#{:02x}{:02x}{:02x}", r, g, b),
-                _ => format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a),
+            let digits = if is_short { short.map(|(d, _)| d) } else { arr };
+            let order = format.hex_byte_order().unwrap();
+            let hex: String = hex_bytes(order, write_alpha(arr[3] != 255))
+                .map(|i| {
+                    if is_short {
+                        format!("{:x}", digits[i])
+                    } else {
+                        format!("{:02x}", digits[i])
+                    }
+                })
+                .collect();
+            let hex = format!("#{hex}");
+            if style.uppercase_hex == Some(true) {
+                hex.to_uppercase()
+            } else {
+                hex
             }
         }
         ColorFormat::Oklch => {
             let c = Oklcha::from(fallback);
-            format!(
-                "oklch({}% {} {}{})",
-                num(c.lightness * 100., 1),
-                num(c.chroma, 4),
-                num(c.hue, 1),
-                css_alpha(c.alpha)
-            )
+            let args = [
+                channel(0, c.lightness.max(0.), 1., 4, Percent),
+                channel(1, c.chroma.max(0.), 0.4, 4, Number),
+                hue(2, c.hue),
+            ];
+            css_function(style, "oklch", false, &args, css_alpha(c.alpha))
         }
-        ColorFormat::Rgb => {
-            let c = Srgba::from(fallback).to_u8_array_no_alpha();
-            format!(
-                "rgb({} {} {}{})",
-                c[0],
-                c[1],
-                c[2],
-                css_alpha(fallback.alpha)
-            )
+        ColorFormat::Oklab => {
+            let c = Oklaba::from(fallback);
+            let args = [
+                channel(0, c.lightness.max(0.), 1., 4, Percent),
+                channel(1, c.a, 0.4, 4, Number),
+                channel(2, c.b, 0.4, 4, Number),
+            ];
+            css_function(style, "oklab", false, &args, css_alpha(c.alpha))
         }
-        ColorFormat::RgbLegacy => {
-            let c = Srgba::from(fallback).to_u8_array_no_alpha();
-            format!(
-                "rgb({}, {}, {}{})",
-                c[0],
-                c[1],
-                c[2],
-                css_legacy_alpha(fallback.alpha)
-            )
+        ColorFormat::Rgb | ColorFormat::RgbLegacy => {
+            let c = Srgba::from(fallback).to_f32_array_no_alpha();
+            let args: Vec<_> = (0..3)
+                .map(|i| channel(i, c[i].clamp(0., 1.) * 255., 255., 0, Number))
+                .collect();
+            let legacy = format == ColorFormat::RgbLegacy;
+            css_function(style, "rgb", legacy, &args, css_alpha(fallback.alpha))
         }
-        ColorFormat::Hsl => {
+        ColorFormat::Hsl | ColorFormat::HslLegacy => {
             let c = Hsla::from(fallback);
-            format!(
-                "hsl({} {}% {}%{})",
-                num(c.hue, 1),
-                num(c.saturation * 100., 1),
-                num(c.lightness * 100., 1),
-                css_alpha(c.alpha)
-            )
+            let args = [
+                hue(0, c.hue),
+                channel(1, c.saturation.max(0.) * 100., 100., 1, Percent),
+                channel(2, c.lightness.max(0.) * 100., 100., 1, Percent),
+            ];
+            let legacy = format == ColorFormat::HslLegacy;
+            css_function(style, "hsl", legacy, &args, css_alpha(c.alpha))
         }
-        ColorFormat::HslLegacy => {
-            let c = Hsla::from(fallback);
-            format!(
-                "hsl({}, {}%, {}%{})",
-                num(c.hue, 1),
-                num(c.saturation * 100., 1),
-                num(c.lightness * 100., 1),
-                css_legacy_alpha(c.alpha)
-            )
+        ColorFormat::Hwb => {
+            let c = Hwba::from(fallback);
+            let args = [
+                hue(0, c.hue),
+                channel(1, c.whiteness.max(0.) * 100., 100., 1, Percent),
+                channel(2, c.blackness.max(0.) * 100., 100., 1, Percent),
+            ];
+            css_function(style, "hwb", false, &args, css_alpha(c.alpha))
         }
-        ColorFormat::HexLiteral => {
-            let [r, g, b, a] = Srgba::from(fallback).to_u8_array();
-            if use_alpha {
-                format!("0x{:02X}{:02X}{:02X}{:02X}", a, r, g, b)
+        ColorFormat::Lab => {
+            let c = CieLaba::from(fallback);
+            let args = [
+                channel(0, c.lightness.max(0.), 100., 2, Number),
+                channel(1, c.a, 125., 2, Number),
+                channel(2, c.b, 125., 2, Number),
+            ];
+            css_function(style, "lab", false, &args, css_alpha(c.alpha))
+        }
+        ColorFormat::Lch => {
+            let c = CieLcha::from(fallback);
+            let args = [
+                channel(0, c.lightness.max(0.), 100., 2, Number),
+                channel(1, c.chroma.max(0.), 150., 2, Number),
+                hue(2, c.hue),
+            ];
+            css_function(style, "lch", false, &args, css_alpha(c.alpha))
+        }
+        ColorFormat::HexLiteral
+        | ColorFormat::HexLiteralRgba
+        | ColorFormat::HexLiteralAbgr
+        | ColorFormat::Colorref => {
+            let c = Srgba::from(fallback).to_u8_array();
+            let order = format.hex_byte_order().unwrap();
+            let alpha = format != ColorFormat::Colorref && write_alpha(use_alpha);
+            let mut hex: String = hex_bytes(order, alpha)
+                .map(|i| format!("{:02X}", c[i]))
+                .collect();
+            if format == ColorFormat::Colorref {
+                hex.insert_str(0, "00");
+            }
+            if style.uppercase_hex == Some(false) {
+                format!("0x{}", hex.to_lowercase())
+            } else {
+                format!("0x{}", hex)
+            }
+        }
+        ColorFormat::Rgb565
+        | ColorFormat::Bgr565
+        | ColorFormat::Rgb332
+        | ColorFormat::Argb4444
+        | ColorFormat::Rgb444 => {
+            let layout = format.packed_layout().unwrap();
+            let value = pack_hex(fallback.into(), layout);
+            let width = format.packed_digits();
+            if style.uppercase_hex == Some(false) {
+                format!("0x{value:0width$x}")
             } else {
-                format!("0x{:02X}{:02X}{:02X}", r, g, b)
+                format!("0x{value:0width$X}")
             }
         }
         ColorFormat::RawRgb => {
             let c = Srgba::from(fallback).to_u8_array();
-            format!(
-                "{}, {}, {}{}",
-                c[0],
-                c[1],
-                c[2],
-                raw_alpha_u8(c[3], use_alpha)
-            )
+            let len = if write_alpha(use_alpha) { 4 } else { 3 };
+            let values: Vec<_> = c[..len].iter().map(|c| c.to_string()).collect();
+            raw_components(style, &values)
+        }
+        ColorFormat::RawRgb10 | ColorFormat::RawRgb16 => {
+            let max = int_components_max(format);
+            let c = Srgba::from(fallback).to_f32_array();
+            let len = if write_alpha(use_alpha) { 4 } else { 3 };
+            let values: Vec<_> = c[..len]
+                .iter()
+                .map(|c| ((c.clamp(0., 1.) * max as f32).round() as u16).to_string())
+                .collect();
+            raw_components(style, &values)
+        }
+        ColorFormat::PackedArgb => {
+            let [r, g, b, a] = Srgba::from(fallback).to_u8_array();
+            let a = if write_alpha(use_alpha) { a } else { 0 };
+            u32::from_be_bytes([a, r, g, b]).to_string()
+        }
+        ColorFormat::PackedRgba => {
+            u32::from_be_bytes(Srgba::from(fallback).to_u8_array()).to_string()
+        }
+        ColorFormat::RawRgbFloat | ColorFormat::RawRgbLinear | ColorFormat::RawOklch => {
+            let (c, hue_decimals) = match format {
+                ColorFormat::RawRgbFloat => (Srgba::from(fallback).to_f32_array(), 4),
+                ColorFormat::RawRgbLinear => (fallback.to_f32_array(), 4),
+                _ => (Oklcha::from(fallback).to_f32_array(), 2),
+            };
+            let mut values = vec![raw(c[0], 0, 4), raw(c[1], 1, 4), raw(c[2], 2, hue_decimals)];
+            if write_alpha(use_alpha) {
+                values.push(raw(c[3], 3, 3));
+            }
+            raw_components(style, &values)
+        }
+        ColorFormat::ColorSrgb
+        | ColorFormat::ColorSrgbLinear
+        | ColorFormat::ColorDisplayP3
+        | ColorFormat::ColorA98Rgb
+        | ColorFormat::ColorProphotoRgb
+        | ColorFormat::ColorRec2020
+        | ColorFormat::ColorXyzD50
+        | ColorFormat::ColorXyzD65 => {
+            let space = format.css_color_space().unwrap();
+            let c = space.to_values(fallback);
+            let args = [
+                space.to_string(),
+                channel(0, c[0], 1., 4, Number),
+                channel(1, c[1], 1., 4, Number),
+                channel(2, c[2], 1., 4, Number),
+            ];
+            css_function(style, "color", false, &args, css_alpha(c[3]))
+        }
+        ColorFormat::Glsl
+        | ColorFormat::GlslLinear
+        | ColorFormat::Wgsl
+        | ColorFormat::WgslLinear
+        | ColorFormat::Hlsl
+        | ColorFormat::HlslLinear
+        | ColorFormat::BevySrgb
+        | ColorFormat::BevyLinearRgb
+        | ColorFormat::BevyOklch
+        | ColorFormat::UnityColor
+        | ColorFormat::UnityColor32
+        | ColorFormat::GodotColor
+        | ColorFormat::GodotColor8
+        | ColorFormat::FlutterArgb => {
+            let (rgb, rgba) = format.call_names().unwrap();
+            let c = format.call_values(fallback);
+            let (name, len) = if rgb.is_empty() || write_alpha(use_alpha) {
+                (rgba[0], 4)
+            } else {
+                (rgb[0], 3)
+            };
+            let values: Vec<_> = (0..len)
+                .map(|i| {
+                    if format.has_byte_components() {
+                        ((c[i] * 255.).round().clamp(0., 255.) as u8).to_string()
+                    } else {
+                        let decimals = match (format, i) {
+                            (_, 3) => 3,
+                            (ColorFormat::BevyOklch, 2) => 2,
+                            _ => 4,
+                        };
+                        let value = raw(c[i], i, decimals);
+                        // C# needs the suffix for float literals
+                        if format == ColorFormat::UnityColor {
+                            format!("{value}f")
+                        } else {
+                            value
+                        }
+                    }
+                })
+                .collect();
+            format!("{name}({})", raw_components(style, &values))
+        }
+        ColorFormat::BevyHex | ColorFormat::GodotHex | ColorFormat::TypstHex => {
+            let hex = format_color_styled(fallback, ColorFormat::Hex, true, style, options);
+            match format {
+                ColorFormat::BevyHex => format!("Srgba::hex(\"{hex}\")"),
+                ColorFormat::TypstHex => format!("rgb(\"{hex}\")"),
+                _ => format!("Color(\"{hex}\")"),
+            }
+        }
+        ColorFormat::SwiftuiColor | ColorFormat::UikitColor | ColorFormat::ComposeColor => {
+            let (name, labels, assign, alpha_required) = format.labelled_call().unwrap();
+            let c = Srgba::from(fallback).to_f32_array();
+            let len = if alpha_required || write_alpha(use_alpha) {
+                4
+            } else {
+                3
+            };
+            let args: Vec<_> = (0..len)
+                .map(|i| {
+                    let value = raw(c[i], i, if i == 3 { 3 } else { 4 });
+                    // Kotlin needs the suffix for float literals
+                    if assign == '=' {
+                        format!("{} = {value}f", labels[i])
+                    } else {
+                        format!("{}: {value}", labels[i])
+                    }
+                })
+                .collect();
+            format!("{name}({})", raw_components(style, &args))
+        }
+        ColorFormat::ColorArgbHex => {
+            let [r, g, b, a] = Srgba::from(fallback).to_u8_array();
+            let hex = format!("{:02X}{:02X}{:02X}{:02X}", a, r, g, b);
+            if style.uppercase_hex == Some(false) {
+                format!("Color(0x{})", hex.to_lowercase())
+            } else {
+                format!("Color(0x{})", hex)
+            }
         }
-        ColorFormat::RawRgbFloat => {
+        ColorFormat::FlutterRgbo => {
             let c = Srgba::from(fallback);
-            format!(
-                "{:?}, {:?}, {:?}{}",
-                num(c.red, 4),
-                num(c.green, 4),
-                num(c.blue, 4),
-                raw_alpha(c.alpha, use_alpha)
-            )
+            let mut values: Vec<_> = c.to_u8_array()[..3].iter().map(|c| c.to_string()).collect();
+            values.push(raw(c.alpha, 3, 3));
+            format!("Color.fromRGBO({})", raw_components(style, &values))
         }
-        ColorFormat::RawRgbLinear => {
-            let c = fallback;
-            format!(
-                "{:?}, {:?}, {:?}{}",
-                num(c.red, 4),
-                num(c.green, 4),
-                num(c.blue, 4),
-                raw_alpha(c.alpha, use_alpha)
-            )
+        ColorFormat::X11Rgb => {
+            let digits = if style.short_hex == Some(true) { 2 } else { 4 };
+            let max = ((1 << (4 * digits)) - 1) as f32;
+            let channels: Vec<_> = Srgba::from(fallback).to_f32_array_no_alpha()[..]
+                .iter()
+                .map(|c| {
+                    let hex = format!("{:0digits$x}", (c.clamp(0., 1.) * max).round() as u32);
+                    if style.uppercase_hex == Some(true) {
+                        hex.to_uppercase()
+                    } else {
+                        hex
+                    }
+                })
+                .collect();
+            format!("rgb:{}", channels.join("/"))
+        }
+        ColorFormat::X11Rgbi => {
+            let c = Srgba::from(fallback).to_f32_array_no_alpha();
+            let channels: Vec<_> = (0..3).map(|i| raw(c[i].clamp(0., 1.), i, 4)).collect();
+            format!("rgbi:{}", channels.join("/"))
+        }
+        ColorFormat::AnsiTruecolor | ColorFormat::Ansi256 => {
+            let values = if format == ColorFormat::AnsiTruecolor {
+                let [r, g, b] = Srgba::from(fallback).to_u8_array_no_alpha();
+                format!("{r};{g};{b}")
+            } else {
+                nearest_xterm_color(fallback).0.to_string()
+            };
+            let default = (format == ColorFormat::AnsiTruecolor).then_some("\\e[38;2;");
+            let prefix = style.name.as_deref().or(default).unwrap_or_default();
+            let end = if prefix.contains('[') { "m" } else { "" };
+            format!("{prefix}{values}{end}")
+        }
+        ColorFormat::Cmyk | ColorFormat::DeviceCmyk | ColorFormat::RawCmyk => {
+            let c = Cmyka::from(fallback);
+            let inks = [c.cyan, c.magenta, c.yellow, c.black];
+            // All inks use the style and precision of the first channel
+            let precision = precision[0];
+            match format {
+                ColorFormat::Cmyk => {
+                    let number_style = style.channels[0].unwrap_or(Percent);
+                    let args =
+                        inks.map(|ink| css_channel(ink * 100., 100., 1, number_style, precision));
+                    css_function(style, "cmyk", true, &args, None)
+                }
+                ColorFormat::DeviceCmyk => {
+                    let number_style = style.channels[0].unwrap_or(Percent);
+                    let args = inks.map(|ink| css_channel(ink, 1., 3, number_style, precision));
+                    css_function(style, "device-cmyk", false, &args, css_alpha(c.alpha))
+                }
+                _ => {
+                    let mut values: Vec<_> = inks
+                        .iter()
+                        .map(|ink| num(ink * 100., precision.map_or(1, i32::from)).to_string())
+                        .collect();
+                    if write_alpha(use_alpha) {
+                        values.push(raw(c.alpha, 3, 3));
+                    }
+                    raw_components(style, &values)
+                }
+            }
+        }
+        ColorFormat::Okhsv | ColorFormat::Okhsl => {
+            let (name, [h, s, v, a]) = okhs_values(format, fallback);
+            let args = [
+                hue(0, h),
+                channel(1, s.max(0.), 1., 3, Percent),
+                channel(2, v.max(0.), 1., 3, Percent),
+            ];
+            css_function(style, name, false, &args, css_alpha(a))
+        }
+        ColorFormat::RawOkhsv | ColorFormat::RawOkhsl => {
+            let (_, c) = okhs_values(format, fallback);
+            let mut values = vec![raw(c[0], 0, 2), raw(c[1], 1, 4), raw(c[2], 2, 4)];
+            if write_alpha(use_alpha) {
+                values.push(raw(c[3], 3, 3));
+            }
+            raw_components(style, &values)
+        }
+        ColorFormat::RawXyz | ColorFormat::RawXyzD50 | ColorFormat::RawXyy => {
+            let c = match format {
+                ColorFormat::RawXyz => CssColorSpace::XyzD65.to_values(fallback),
+                ColorFormat::RawXyzD50 => CssColorSpace::XyzD50.to_values(fallback),
+                _ => {
+                    let c = Xyya::from(fallback);
+                    [c.x, c.y, c.luminance, c.alpha]
+                }
+            };
+            let mut values = vec![raw(c[0], 0, 4), raw(c[1], 1, 4), raw(c[2], 2, 4)];
+            if write_alpha(use_alpha) {
+                values.push(raw(c[3], 3, 3));
+            }
+            raw_components(style, &values)
+        }
+        ColorFormat::LatexHtml | ColorFormat::LatexRgb | ColorFormat::LatexRgb8 => {
+            let c = Srgba::from(fallback);
+            let (model, values) = match format {
+                ColorFormat::LatexHtml => {
+                    let [r, g, b, _] = c.to_u8_array();
+                    let hex = format!("{r:02X}{g:02X}{b:02X}");
+                    if style.uppercase_hex == Some(false) {
+                        ("HTML", hex.to_lowercase())
+                    } else {
+                        ("HTML", hex)
+                    }
+                }
+                ColorFormat::LatexRgb => {
+                    let c = c.to_f32_array();
+                    let values = [raw(c[0], 0, 4), raw(c[1], 1, 4), raw(c[2], 2, 4)];
+                    (
+                        "rgb",
+                        values.join(style.separator.as_deref().unwrap_or(",")),
+                    )
+                }
+                _ => {
+                    let values = c.to_u8_array_no_alpha().map(|c| c.to_string());
+                    (
+                        "RGB",
+                        values.join(style.separator.as_deref().unwrap_or(",")),
+                    )
+                }
+            };
+            let prefix = style.name.as_deref().unwrap_or("\\color[");
+            let close = if prefix.ends_with('[') { ']' } else { '}' };
+            format!("{prefix}{model}{close}{{{values}}}")
         }
-        ColorFormat::RawOklch => {
+        ColorFormat::TypstOklch | ColorFormat::TypstLuma => {
+            // Typst wants ratios for lightness and alpha, and only has degrees and radians
+            let alpha = write_alpha(fallback.alpha < 1. || input_has_alpha)
+                .then(|| css_channel(fallback.alpha, 1., 3, Percent, precision[3]));
+            if format == ColorFormat::TypstLuma {
+                let gray = Srgba::gamma_function_inverse(fallback.luminance());
+                let lightness = if style.channels[0] == Some(Percent) {
+                    css_channel(gray, 1., 3, Percent, precision[0])
+                } else {
+                    ((gray.clamp(0., 1.) * 255.).round() as u8).to_string()
+                };
+                return css_function(style, "luma", true, &[lightness], alpha);
+            }
             let c = Oklcha::from(fallback);
-            format!(
-                "{:?}, {:?}, {:?}{}",
-                num(c.lightness, 4),
-                num(c.chroma, 4),
-                num(c.hue, 2),
-                raw_alpha(c.alpha, use_alpha)
-            )
+            let unit = match style.hue_unit {
+                Some(HueUnit::Rad) => HueUnit::Rad,
+                _ => HueUnit::Deg,
+            };
+            let args = [
+                css_channel(c.lightness.max(0.), 1., 4, Percent, precision[0]),
+                channel(1, c.chroma.max(0.), 0.4, 4, Number),
+                css_hue(c.hue, unit, precision[2]),
+            ];
+            css_function(style, "oklch", true, &args, alpha)
         }
+        ColorFormat::Named => nearest_named_color(fallback).0.to_string(),
+        ColorFormat::Template(i) => templates()[i].format(fallback),
     }
 }
 
-#[cfg(not(target_arch = "wasm32"))]
 static UNKNOWN_FORMAT_CANDIDATES: LazyLock<Vec<ColorFormat>> = LazyLock::new(|| {
-    ColorFormat::iter()
+    ColorFormat::all()
         .filter(ColorFormat::is_auto_detectable)
         .collect()
 });
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn parse_color_unknown_format(s: &str) -> Option<(Color, ColorFormat, bool)> {
-    let s = s.trim();
+/// Why a color couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// Byte range of the input where parsing failed
+    pub span: Range<usize>,
+    /// The format that was tried, `None` if no format could be detected
+    pub format: Option<ColorFormat>,
+    /// What was expected, e.g. "expected `%` for hsl saturation"
+    pub expected: String,
+}
 
-    for format in UNKNOWN_FORMAT_CANDIDATES.iter().copied() {
-        if let Some((parsed, use_alpha)) = parse_color_impl(s, format) {
-            return Some((parsed, format, use_alpha));
+impl ParseError {
+    fn new(span: Range<usize>, format: Option<ColorFormat>, expected: impl Into<String>) -> Self {
+        Self {
+            span,
+            format,
+            expected: expected.into(),
         }
     }
-    None
-}
 
-pub fn parse_color(s: &str, input_format: ColorFormat) -> Option<(Color, bool)> {
-    let s = s.trim();
-    parse_color_impl(s, input_format)
-}
+    fn from_winnow(
+        e: winnow::error::ParseError<&str, ContextError>,
+        format: Option<ColorFormat>,
+    ) -> Self {
+        let input = *e.input();
 
-fn parse_color_impl(s: &str, input_format: ColorFormat) -> Option<(Color, bool)> {
-    match input_format {
-        ColorFormat::Hex => parse_hex(s.strip_prefix("#")?, true).map(|(c, _)| (c.into(), true)),
-        ColorFormat::Oklch => oklch_parser.parse(s).ok().map(|c| (c.into(), true)),
-        ColorFormat::Rgb => rgb_parser.parse(s).ok().map(|c| (c.into(), true)),
-        ColorFormat::Hsl => hsl_parser.parse(s).ok().map(|c| (c.into(), true)),
-        ColorFormat::RgbLegacy => rgb_legacy_parser.parse(s).ok().map(|c| (c.into(), true)),
-        ColorFormat::HslLegacy => hsl_legacy_parser.parse(s).ok().map(|c| (c.into(), true)),
-        ColorFormat::HexLiteral => parse_hex(s.strip_prefix("0x")?, false)
-            .map(|(c, has_alpha)| {
-                let mut parts = c.to_f32_array();
-                // Read as ARGB instead of RGBA
-                if has_alpha {
-                    parts.rotate_right(3);
-                }
-                (Srgba::from_f32_array(parts).into(), has_alpha)
-            })?
-            .into(),
-        ColorFormat::RawRgb => color_components_u8_parser::<Srgba>.parse(s).ok()?.into(),
-        ColorFormat::RawRgbFloat => color_components_parser::<Srgba>.parse(s).ok()?.into(),
-        ColorFormat::RawRgbLinear => color_components_parser::<LinearRgba>.parse(s).ok()?.into(),
-        ColorFormat::RawOklch => color_components_parser::<Oklcha>.parse(s).ok()?.into(),
+        // The innermost context is the most specific
+        let context = e.inner().context();
+        let (mut expected, mut label) = (None, None);
+        for c in context {
+            match c {
+                StrContext::Expected(v) if expected.is_none() => expected = Some(v),
+                StrContext::Label(l) if label.is_none() => label = Some(*l),
+                _ => {}
+            }
+        }
+        let expected = expected.map(|v| match v {
+            StrContextValue::CharLiteral(c) => format!("`{c}`"),
+            StrContextValue::StringLiteral(s) => format!("`{s}`"),
+            v => v.to_string(),
+        });
+
+        let mut offset = e.offset();
+        if expected.is_none() && label.is_none() {
+            // Point at the unexpected token rather than the whitespace before it
+            offset = input.len() - input[offset..].trim_start().len();
+        }
+        let span = token_span(input, offset);
+
+        let message = match (expected, label) {
+            (Some(expected), Some(label)) => format!("expected {expected} for {label}"),
+            (Some(expected), None) => format!("expected {expected}"),
+            (None, Some(label)) => format!("invalid {label}"),
+            (None, None) if span.is_empty() => "unexpected end of input".to_string(),
+            (None, None) => format!("unexpected `{}`", &input[span.clone()]),
+        };
+        Self::new(span, format, message)
     }
-}
+
+    fn shifted(mut self, offset: usize) -> Self {
+        self.span = self.span.start + offset..self.span.end + offset;
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.format {
+            Some(format) => write!(f, "invalid {format}: {}", self.expected),
+            None => write!(f, "{}", self.expected),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// The span of the token starting at `offset`, or of a single delimiter character
+fn token_span(input: &str, offset: usize) -> Range<usize> {
+    let rest = &input[offset..];
+    let len = match rest.find(|c: char| c.is_whitespace() || matches!(c, ',' | '/' | '(' | ')')) {
+        Some(0) => rest.chars().next().map_or(0, char::len_utf8),
+        Some(len) => len,
+        None => rest.len(),
+    };
+    offset..offset + len
+}
+
+pub fn expected(description: &'static str) -> StrContext {
+    StrContext::Expected(StrContextValue::Description(description))
+}
+
+fn parse_with<'a, O>(
+    mut parser: impl Parser<&'a str, O, ErrMode<ContextError>>,
+    s: &'a str,
+    format: Option<ColorFormat>,
+) -> Result<O, ParseError> {
+    parser
+        .parse(s)
+        .map_err(|e| ParseError::from_winnow(e, format))
+}
+
+fn is_color_mix(s: &str) -> bool {
+    s.get(..10)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("color-mix("))
+}
+
+/// How much less confident than the best match another match can be to make the detection ambiguous
+const AMBIGUITY_MARGIN: f32 = 0.2;
+
+/// A format that an input parses as, see [`detect_formats`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatMatch {
+    pub format: ColorFormat,
+    pub color: Color,
+    pub use_alpha: bool,
+    /// How likely it is that the input was meant as this format, from 0 to 1
+    pub confidence: f32,
+}
+
+/// All formats that an input parses as, the preferred one first
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormatDetection {
+    pub matches: Vec<FormatMatch>,
+    /// Several formats are about as likely and no hint decided between them
+    pub ambiguous: bool,
+}
+
+impl FormatDetection {
+    pub fn best(&self) -> &FormatMatch {
+        &self.matches[0]
+    }
+
+    /// The matches that are about as likely as the most confident one
+    pub fn likely(&self) -> impl Iterator<Item = &FormatMatch> {
+        let best = self.matches.iter().map(|m| m.confidence).fold(0., f32::max);
+        self.matches
+            .iter()
+            .filter(move |m| m.confidence >= best - AMBIGUITY_MARGIN)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn parse_color_unknown_format(s: &str) -> Result<(Color, ColorFormat, bool), ParseError> {
+    detect_formats(s, &[]).map(|detection| {
+        let best = detection.best();
+        (best.color, best.format, best.use_alpha)
+    })
+}
+
+/// Parses `s` with every auto detectable format and ranks the matches by confidence.
+/// Among the likely matches, the first format in `hints` that matched is preferred.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn detect_formats(s: &str, hints: &[ColorFormat]) -> Result<FormatDetection, ParseError> {
+    let offset = s.len() - s.trim_start().len();
+    let s = s.trim();
+
+    if is_color_mix(s) {
+        return parse_with(color_mix_parser, s, None)
+            .map(|(color, format)| FormatDetection {
+                matches: vec![FormatMatch {
+                    format,
+                    color,
+                    use_alpha: true,
+                    confidence: 1.,
+                }],
+                ambiguous: false,
+            })
+            .map_err(|e| e.shifted(offset));
+    }
+
+    let mut matches = Vec::new();
+    // Report the error of the format that got the furthest
+    let mut furthest: Option<ParseError> = None;
+    for format in UNKNOWN_FORMAT_CANDIDATES.iter().copied() {
+        match parse_color_impl(s, format) {
+            Ok((color, use_alpha)) => {
+                let confidence = format.confidence(s);
+                if confidence > 0. {
+                    matches.push(FormatMatch {
+                        format,
+                        color,
+                        use_alpha,
+                        confidence,
+                    });
+                }
+            }
+            Err(e) => {
+                if furthest
+                    .as_ref()
+                    .is_none_or(|f| e.span.start > f.span.start)
+                {
+                    furthest = Some(e);
+                }
+            }
+        }
+    }
+
+    if matches.is_empty() {
+        let error = match furthest {
+            Some(e) if e.span.start > 0 => e,
+            _ => ParseError::new(0..s.len(), None, "unknown color format"),
+        };
+        return Err(error.shifted(offset));
+    }
+
+    // The sort is stable, so equally likely formats stay in candidate order
+    matches.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    let mut detection = FormatDetection {
+        matches,
+        ambiguous: false,
+    };
+    let likely = detection.likely().count();
+    let hinted = hints.iter().find_map(|hint| {
+        detection.matches[..likely]
+            .iter()
+            .position(|m| m.format == *hint)
+    });
+    match hinted {
+        Some(i) => detection.matches[..=i].rotate_right(1),
+        None => detection.ambiguous = likely > 1,
+    }
+    Ok(detection)
+}
+
+/// Kinds of colors that [`find_colors_with`] looks for in addition to hex colors,
+/// hex literals, CSS functions and terminal colors
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FindOptions {
+    /// Lists of 3 or 4 numbers like `0.5, 0.5, 0.5`
+    pub raw: bool,
+    /// CSS named colors like `tomato`
+    pub named: bool,
+}
+
+/// The byte range, color, format and alpha usage of a color found in text
+pub type FoundColor = (Range<usize>, Color, ColorFormat, bool);
+
+/// Finds all hex colors, hex literals, CSS color functions and terminal colors in `text`
+#[cfg(not(target_arch = "wasm32"))]
+// Not really dead but my lib system messes with compilation
+#[allow(dead_code)]
+pub fn find_colors(text: &str) -> Vec<FoundColor> {
+    find_colors_with(text, FindOptions::default())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn find_colors_with(text: &str, options: FindOptions) -> Vec<FoundColor> {
+    // A color must not continue a word on either side, e.g. `#abc` in `#abcdefg-id`
+    let is_word = |c: char| c.is_alphanumeric() || matches!(c, '_' | '-');
+
+    let mut found = Vec::new();
+    let mut start = 0;
+    while let Some(c) = text[start..].chars().next() {
+        let before = &text[..start];
+        // Escape sequences end themselves and are usually written right next to text
+        let escape = escape_parser.parse_peek(&text[start..]).is_ok();
+        if (escape
+            || before
+                .chars()
+                .next_back()
+                .is_none_or(|p| !is_word(p) && !matches!(p, '.' | '#')))
+            && let Some(token) = color_token(&text[start..], before, options)
+            && (escape
+                || text[start + token.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(|n| !is_word(n)))
+            && let Ok((color, format, use_alpha)) = parse_color_unknown_format(token)
+        {
+            found.push((start..start + token.len(), color, format, use_alpha));
+            start += token.len();
+        } else {
+            start += c.len_utf8();
+        }
+    }
+    found
+}
+
+/// The part of `s` that could be a color, `before` is the text preceding `s`
+#[cfg(not(target_arch = "wasm32"))]
+fn color_token<'a>(s: &'a str, before: &str, options: FindOptions) -> Option<&'a str> {
+    let hex_token = |prefix: &str| {
+        let len = s[prefix.len()..]
+            .find(|c: char| !c.is_ascii_hexdigit())
+            .map_or(s.len(), |i| prefix.len() + i);
+        &s[..len]
+    };
+
+    // Escape sequences, X11 `rgb:`/`rgbi:` colors and tmux `colour196`, but not `color1`
+    // which is too common as a name in code
+    let terminal = alt((
+        (
+            escape_parser,
+            '[',
+            take_while(1.., |c: char| c.is_ascii_digit() || c == ';'),
+            'm',
+        )
+            .void(),
+        (
+            alt((Caseless("rgbi:"), Caseless("rgb:"))),
+            take_while(1.., |c: char| {
+                c.is_ascii_hexdigit() || matches!(c, '/' | '.')
+            }),
+        )
+            .void(),
+        (Caseless("colour"), digit1).void(),
+    ))
+    .take()
+    .parse_peek(s);
+
+    let first = s.chars().next()?;
+    if let Ok((_, token)) = terminal {
+        Some(token)
+    } else if first == '#' {
+        Some(hex_token("#"))
+    } else if first == '\\' {
+        LATEX_MODELS.iter().find_map(|model| {
+            latex_color_parser(model, take_till(0.., '}'))
+                .take()
+                .parse_peek(s)
+                .ok()
+                .map(|(_, token)| token)
+        })
+    } else if s.starts_with("0x") {
+        Some(hex_token("0x"))
+    } else if first.is_ascii_alphabetic() {
+        // Also engine constructors like `new Color32(..)` and `Color::srgb(..)`
+        let call_name = take_while(1.., |c: char| {
+            c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.' | '<' | '>')
+        });
+        let function = (opt(("new", space1).void()), call_name, css_parens_parser)
+            .take()
+            .parse_peek(s);
+        match function {
+            Ok((_, token)) => Some(token),
+            Err(_) if options.named => {
+                let name = s.find(|c: char| !c.is_ascii_alphanumeric() && c != '-');
+                Some(&s[..name.unwrap_or(s.len())])
+            }
+            Err(_) => None,
+        }
+    } else if options.raw && !before.trim_end().ends_with(',') {
+        let (rest, token) = separated(3..=4, number_parser, comma_parser)
+            .map(|_: Vec<f32>| ())
+            .take()
+            .parse_peek(s)
+            .ok()?;
+        // Longer lists of numbers aren't colors
+        (!rest.trim_start().starts_with(',')).then_some(token)
+    } else {
+        None
+    }
+}
+
+pub fn parse_color(s: &str, input_format: ColorFormat) -> Result<(Color, bool), ParseError> {
+    let offset = s.len() - s.trim_start().len();
+    parse_color_impl(s.trim(), input_format).map_err(|e| e.shifted(offset))
+}
+
+fn parse_color_impl(s: &str, input_format: ColorFormat) -> Result<(Color, bool), ParseError> {
+    let format = Some(input_format);
+
+    // Mixes are valid anywhere a CSS color function is
+    if input_format.is_css_function() && is_color_mix(s) {
+        return parse_with(color_mix_parser, s, format).map(|(color, _)| (color, true));
+    }
+
+    let hex_digits = |prefix: &str| {
+        let Some(hex) = s.strip_prefix(prefix) else {
+            return Err(ParseError::new(
+                token_span(s, 0),
+                format,
+                format!("expected `{prefix}`"),
+            ));
+        };
+        match hex.char_indices().find(|(_, c)| !c.is_ascii_hexdigit()) {
+            Some((i, c)) => {
+                let start = prefix.len() + i;
+                Err(ParseError::new(
+                    start..start + c.len_utf8(),
+                    format,
+                    "expected a hex digit",
+                ))
+            }
+            None => Ok(hex),
+        }
+    };
+
+    match input_format {
+        ColorFormat::Hex | ColorFormat::HexArgb | ColorFormat::HexAbgr => {
+            let order = input_format.hex_byte_order().unwrap();
+            parse_hex(hex_digits("#")?, true)
+                .map(|(c, has_alpha)| (reorder_hex(c, order, has_alpha).into(), true))
+                .ok_or_else(|| {
+                    ParseError::new(1..s.len(), format, "hex must have 3, 4, 6 or 8 digits")
+                })
+        }
+        ColorFormat::Oklch => parse_with(oklch_parser, s, format).map(|c| (c.into(), true)),
+        ColorFormat::Oklab => parse_with(oklab_parser, s, format).map(|c| (c.into(), true)),
+        ColorFormat::Rgb => parse_with(rgb_parser, s, format).map(|c| (c.into(), true)),
+        ColorFormat::Hsl => parse_with(hsl_parser, s, format).map(|c| (c.into(), true)),
+        ColorFormat::Hwb => parse_with(hwb_parser, s, format).map(|c| (c.into(), true)),
+        ColorFormat::Lab => {
+            parse_with(lab_parser, s, format).map(|c| (LinearRgba::from(c).into(), true))
+        }
+        ColorFormat::Lch => {
+            parse_with(lch_parser, s, format).map(|c| (LinearRgba::from(c).into(), true))
+        }
+        ColorFormat::RgbLegacy => {
+            parse_with(rgb_legacy_parser, s, format).map(|c| (c.into(), true))
+        }
+        ColorFormat::HslLegacy => {
+            parse_with(hsl_legacy_parser, s, format).map(|c| (c.into(), true))
+        }
+        ColorFormat::HexLiteral
+        | ColorFormat::HexLiteralRgba
+        | ColorFormat::HexLiteralAbgr
+        | ColorFormat::Colorref => {
+            let order = input_format.hex_byte_order().unwrap();
+            parse_hex(hex_digits("0x")?, false)
+                .map(|(c, has_alpha)| {
+                    let c = reorder_hex(c, order, has_alpha);
+                    if input_format == ColorFormat::Colorref {
+                        (Srgba { alpha: 1., ..c }.into(), false)
+                    } else {
+                        (c.into(), has_alpha)
+                    }
+                })
+                .ok_or_else(|| {
+                    ParseError::new(2..s.len(), format, "hex literal must have 6 or 8 digits")
+                })
+        }
+        ColorFormat::Rgb565
+        | ColorFormat::Bgr565
+        | ColorFormat::Rgb332
+        | ColorFormat::Argb4444
+        | ColorFormat::Rgb444 => {
+            let digits = input_format.packed_digits();
+            parse_packed_hex(hex_digits("0x")?, input_format.packed_layout().unwrap())
+                .map(|c| (c.into(), input_format == ColorFormat::Argb4444))
+                .ok_or_else(|| {
+                    ParseError::new(
+                        2..s.len(),
+                        format,
+                        format!("hex literal must have 1 to {digits} digits"),
+                    )
+                })
+        }
+        ColorFormat::RawRgb => parse_with(color_components_u8_parser::<Srgba>, s, format),
+        ColorFormat::RawRgb10 | ColorFormat::RawRgb16 => parse_with(
+            color_components_int_parser(int_components_max(input_format)),
+            s,
+            format,
+        ),
+        ColorFormat::PackedArgb => parse_with(packed_int_parser, s, format).map(|value| {
+            let [a, r, g, b] = value.to_be_bytes();
+            // Without alpha bits the number is plain 0xRRGGBB
+            if value <= 0xffffff {
+                (Srgba::rgb_u8(r, g, b).into(), false)
+            } else {
+                (Srgba::rgba_u8(r, g, b, a).into(), true)
+            }
+        }),
+        ColorFormat::PackedRgba => parse_with(packed_int_parser, s, format)
+            .map(|value| (Srgba::from_u8_array(value.to_be_bytes()).into(), true)),
+        ColorFormat::RawRgbFloat => parse_with(color_components_parser::<Srgba>, s, format),
+        ColorFormat::RawRgbLinear => parse_with(color_components_parser::<LinearRgba>, s, format),
+        ColorFormat::RawOklch => parse_with(color_components_parser::<Oklcha>, s, format),
+        ColorFormat::ColorSrgb
+        | ColorFormat::ColorSrgbLinear
+        | ColorFormat::ColorDisplayP3
+        | ColorFormat::ColorA98Rgb
+        | ColorFormat::ColorProphotoRgb
+        | ColorFormat::ColorRec2020
+        | ColorFormat::ColorXyzD50
+        | ColorFormat::ColorXyzD65 => {
+            let space = input_format.css_color_space().unwrap();
+            parse_with(css_color_function_parser(space), s, format)
+                .map(|values| (space.to_linear_rgba(values).into(), true))
+        }
+        ColorFormat::Glsl
+        | ColorFormat::GlslLinear
+        | ColorFormat::Wgsl
+        | ColorFormat::WgslLinear
+        | ColorFormat::Hlsl
+        | ColorFormat::HlslLinear
+        | ColorFormat::BevySrgb
+        | ColorFormat::BevyLinearRgb
+        | ColorFormat::BevyOklch
+        | ColorFormat::UnityColor
+        | ColorFormat::UnityColor32
+        | ColorFormat::GodotColor
+        | ColorFormat::GodotColor8
+        | ColorFormat::FlutterArgb => {
+            let (rgb, rgba) = input_format.call_names().unwrap();
+            let component = if input_format.has_byte_components() {
+                byte_component_parser
+            } else {
+                float_component_parser
+            };
+            // Only shader vectors can be made from a single value
+            let splat = matches!(
+                input_format,
+                ColorFormat::Glsl
+                    | ColorFormat::GlslLinear
+                    | ColorFormat::Wgsl
+                    | ColorFormat::WgslLinear
+                    | ColorFormat::Hlsl
+                    | ColorFormat::HlslLinear
+            );
+            parse_with(call_parser(rgb, rgba, splat, component), s, format)
+                .map(|(values, use_alpha)| (input_format.call_color(values), use_alpha))
+        }
+        ColorFormat::BevyHex | ColorFormat::GodotHex | ColorFormat::TypstHex => {
+            let name = match input_format {
+                ColorFormat::BevyHex => "Srgba::hex",
+                ColorFormat::TypstHex => "rgb",
+                _ => "Color",
+            };
+            let hex = parse_with(hex_string_call_parser(name), s, format)?;
+            parse_hex(hex, true)
+                .map(|(c, _)| (c.into(), true))
+                .ok_or_else(|| {
+                    let start = s.rfind(hex).unwrap_or(0);
+                    ParseError::new(
+                        start..start + hex.len(),
+                        format,
+                        "hex must have 3, 4, 6 or 8 digits",
+                    )
+                })
+        }
+        ColorFormat::SwiftuiColor | ColorFormat::UikitColor | ColorFormat::ComposeColor => {
+            let (name, labels, assign, alpha_required) = input_format.labelled_call().unwrap();
+            parse_with(
+                labelled_call_parser(name, labels, assign, alpha_required),
+                s,
+                format,
+            )
+            .map(|(values, use_alpha)| (Srgba::from_f32_array(values).into(), use_alpha))
+        }
+        ColorFormat::ColorArgbHex => parse_with(argb_hex_call_parser, s, format)
+            .map(|values| (Srgba::from_f32_array(values).into(), true)),
+        ColorFormat::FlutterRgbo => parse_with(flutter_rgbo_parser, s, format)
+            .map(|values| (Srgba::from_f32_array(values).into(), true)),
+        ColorFormat::X11Rgb => parse_with(x11_rgb_parser, s, format).map(|c| (c.into(), false)),
+        ColorFormat::X11Rgbi => parse_with(x11_rgbi_parser, s, format).map(|c| (c.into(), false)),
+        ColorFormat::AnsiTruecolor => {
+            parse_with(ansi_truecolor_parser, s, format).map(|c| (c.into(), false))
+        }
+        ColorFormat::Ansi256 => {
+            parse_with(ansi256_parser, s, format).map(|i| (xterm_color(i).into(), false))
+        }
+        ColorFormat::Cmyk => {
+            parse_with(cmyk_parser, s, format).map(|c| (Srgba::from(c).into(), false))
+        }
+        ColorFormat::DeviceCmyk => {
+            parse_with(device_cmyk_parser, s, format).map(|c| (Srgba::from(c).into(), true))
+        }
+        ColorFormat::RawCmyk => parse_with(raw_cmyk_parser, s, format)
+            .map(|(c, use_alpha)| (Srgba::from(c).into(), use_alpha)),
+        ColorFormat::Okhsv => {
+            parse_with(okhsv_parser, s, format).map(|c| (LinearRgba::from(c).into(), true))
+        }
+        ColorFormat::Okhsl => {
+            parse_with(okhsl_parser, s, format).map(|c| (LinearRgba::from(c).into(), true))
+        }
+        ColorFormat::RawOkhsv => {
+            parse_with(raw_okhs_parser, s, format).map(|([h, s, v, a], use_alpha)| {
+                (LinearRgba::from(Okhsva::new(h, s, v, a)).into(), use_alpha)
+            })
+        }
+        ColorFormat::RawOkhsl => {
+            parse_with(raw_okhs_parser, s, format).map(|([h, s, l, a], use_alpha)| {
+                (LinearRgba::from(Okhsla::new(h, s, l, a)).into(), use_alpha)
+            })
+        }
+        ColorFormat::RawXyz | ColorFormat::RawXyzD50 | ColorFormat::RawXyy => {
+            parse_with(raw_values_parser, s, format).map(|(values, use_alpha)| {
+                let color = match input_format {
+                    ColorFormat::RawXyz => CssColorSpace::XyzD65.to_linear_rgba(values),
+                    ColorFormat::RawXyzD50 => CssColorSpace::XyzD50.to_linear_rgba(values),
+                    _ => {
+                        let [x, y, luminance, alpha] = values;
+                        Xyya::new(x, y, luminance, alpha).into()
+                    }
+                };
+                (color.into(), use_alpha)
+            })
+        }
+        ColorFormat::LatexHtml => parse_with(
+            latex_color_parser(
+                "HTML",
+                take_while(6, |c: char| c.is_ascii_hexdigit())
+                    .map(|hex| parse_hex(hex, false).unwrap().0)
+                    .context(expected("6 hex digits")),
+            ),
+            s,
+            format,
+        )
+        .map(|c| (c.into(), false)),
+        ColorFormat::LatexRgb => parse_with(
+            latex_color_parser(
+                "rgb",
+                separated(3, number_parser, comma_parser)
+                    .verify(|values: &Vec<f32>| values.iter().all(|v| (0. ..=1.).contains(v)))
+                    .context(expected("numbers from 0 to 1")),
+            ),
+            s,
+            format,
+        )
+        .map(|c: Vec<f32>| (Srgba::rgb(c[0], c[1], c[2]).into(), false)),
+        ColorFormat::LatexRgb8 => parse_with(
+            latex_color_parser("RGB", separated(3, u8_parser, comma_parser)),
+            s,
+            format,
+        )
+        .map(|c: Vec<u8>| (Srgba::rgb_u8(c[0], c[1], c[2]).into(), false)),
+        ColorFormat::TypstOklch => {
+            parse_with(typst_oklch_parser, s, format).map(|c| (c.into(), true))
+        }
+        ColorFormat::TypstLuma => {
+            parse_with(typst_luma_parser, s, format).map(|c| (c.into(), true))
+        }
+        ColorFormat::Named => parse_named_color(s)
+            .map(|c| (c.into(), true))
+            .ok_or_else(|| ParseError::new(0..s.len(), format, "unknown color name")),
+        ColorFormat::Template(i) => parse_with(
+            |input: &mut &str| templates()[i].color_parser(input),
+            s,
+            format,
+        ),
+    }
+}
 
 // Modified from bevy_color to be more general
 pub fn parse_hex(hex: &str, allow_short: bool) -> Option<(Srgba, bool)> {
@@ -303,24 +2091,84 @@ pub fn parse_hex(hex: &str, allow_short: bool) -> Option<(Srgba, bool)> {
     .into()
 }
 
+/// The channels of `order` that are written, see [`ColorFormat::hex_byte_order`]
+fn hex_bytes(order: [usize; 4], alpha: bool) -> impl Iterator<Item = usize> {
+    order.into_iter().filter(move |i| alpha || *i != 3)
+}
+
+/// Moves the channels of `c`, which [`parse_hex`] reads in RGBA order, to their places in
+/// the byte order of a format
+fn reorder_hex(c: Srgba, order: [usize; 4], has_alpha: bool) -> Srgba {
+    let mut channels = [1.; 4];
+    for (i, value) in hex_bytes(order, has_alpha).zip(c.to_f32_array()) {
+        channels[i] = value;
+    }
+    Srgba::from_f32_array(channels)
+}
+
+/// Parses the digits of a reduced-depth packed format like RGB565 `F81F`
+pub fn parse_packed_hex(hex: &str, layout: &[(usize, u32)]) -> Option<Srgba> {
+    let bits: u32 = layout.iter().map(|(_, b)| b).sum();
+    if hex.is_empty() || hex.len() > bits.div_ceil(4) as usize {
+        return None;
+    }
+    let value = u16::from_str_radix(hex, 16).ok()?;
+    (u32::from(value) < 1 << bits).then(|| unpack_hex(value, layout))
+}
+
+fn unpack_hex(mut value: u16, layout: &[(usize, u32)]) -> Srgba {
+    let mut c = [1.; 4];
+    for &(i, bits) in layout.iter().rev() {
+        let max = (1 << bits) - 1;
+        c[i] = (value & max) as f32 / max as f32;
+        value >>= bits;
+    }
+    Srgba::from_f32_array(c)
+}
+
+fn pack_hex(color: Srgba, layout: &[(usize, u32)]) -> u16 {
+    let c = color.to_f32_array();
+    layout.iter().fold(0, |acc, &(i, bits)| {
+        let max = (1 << bits) - 1;
+        (acc << bits) | (c[i].clamp(0., 1.) * max as f32).round() as u16
+    })
+}
+
 const FLOAT_OPTIONS: lexical_parse_float::Options = lexical_parse_float::Options::new();
 
-fn js_float_parser(input: &mut &str) -> ModalResult<f32> {
-    alt(((digit1, opt(('.', digit0))).void(), ('.', digit1).void()))
+/// CSS `<number>`: optional sign, digits with an optional fraction and an optional exponent
+pub fn number_parser(input: &mut &str) -> ModalResult<f32> {
+    (
+        opt(one_of(['+', '-'])),
+        alt(((digit1, opt(('.', digit0))).void(), ('.', digit1).void())),
+        opt((one_of(['e', 'E']), opt(one_of(['+', '-'])), digit1)),
+    )
         .take()
         .try_map(|s: &str| {
-            f32::from_lexical_with_options::<{ lexical_parse_float::format::JAVASCRIPT_LITERAL }>(
+            f32::from_lexical_with_options::<{ lexical_parse_float::format::STANDARD }>(
                 s.as_bytes(),
                 &FLOAT_OPTIONS,
             )
         })
+        .context(expected("number"))
+        .parse_next(input)
+}
+
+fn comma_parser(input: &mut &str) -> ModalResult<()> {
+    (space0, ',', space0)
+        .void()
+        .context(StrContext::Expected(StrContextValue::CharLiteral(',')))
         .parse_next(input)
 }
 
+fn space_parser(input: &mut &str) -> ModalResult<()> {
+    space1.void().context(expected("space")).parse_next(input)
+}
+
 fn color_components_parser<C: ColorToComponents + Into<Color>>(
     input: &mut &str,
 ) -> ModalResult<(Color, bool)> {
-    separated(3..=4, js_float_parser, (space0, ',', space0))
+    separated(3..=4, number_parser, comma_parser)
         .map(|parts: Vec<f32>| {
             if parts.len() == 3 {
                 (
@@ -339,8 +2187,10 @@ fn color_components_u8_parser<C: ColorToPacked + Into<Color>>(
 ) -> ModalResult<(Color, bool)> {
     separated(
         3..=4,
-        digit1.try_map(|s: &str| s.parse::<u8>()),
-        (space0, ',', space0),
+        digit1
+            .try_map(|s: &str| s.parse::<u8>())
+            .context(expected("integer from 0 to 255")),
+        comma_parser,
     )
     .map(|parts: Vec<u8>| {
         if parts.len() == 3 {
@@ -355,6 +2205,57 @@ fn color_components_u8_parser<C: ColorToPacked + Into<Color>>(
     .parse_next(input)
 }
 
+/// The largest component of the deeper integer formats
+fn int_components_max(format: ColorFormat) -> u16 {
+    match format {
+        ColorFormat::RawRgb10 => 1023,
+        _ => u16::MAX,
+    }
+}
+
+/// Like [`color_components_u8_parser`], but for sRGB integers from 0 to `max`
+fn color_components_int_parser<'a>(
+    max: u16,
+) -> impl Parser<&'a str, (Color, bool), ErrMode<ContextError>> {
+    let label = if max == 1023 {
+        "integer from 0 to 1023"
+    } else {
+        "integer from 0 to 65535"
+    };
+    separated(
+        3..=4,
+        digit1
+            .try_map(|s: &str| s.parse::<u16>())
+            .verify(move |c| *c <= max)
+            .context(expected(label)),
+        comma_parser,
+    )
+    .map(move |parts: Vec<u16>| {
+        let mut c = [1.; 4];
+        for (c, part) in c.iter_mut().zip(&parts) {
+            *c = *part as f32 / max as f32;
+        }
+        (Srgba::from_f32_array(c).into(), parts.len() == 4)
+    })
+}
+
+/// A decimal integer holding a packed color. Negative numbers are read as signed 32-bit
+/// integers, like the ones from Java's `Color.getRGB()`.
+fn packed_int_parser(input: &mut &str) -> ModalResult<u32> {
+    (opt('-'), digit1)
+        .take()
+        .try_map(|s: &str| s.parse::<i64>())
+        .verify_map(|n| {
+            if n < 0 {
+                i32::try_from(n).ok().map(|n| n as u32)
+            } else {
+                u32::try_from(n).ok()
+            }
+        })
+        .context(expected("32-bit integer"))
+        .parse_next(input)
+}
+
 enum CssNum {
     Num(f32),
     Percentage(CssPercentage),
@@ -392,389 +2293,3387 @@ impl CssPercentage {
     }
 
     fn as_u8(&self) -> f32 {
-        self.0 / 100. * 255.
+        self.0 / 100.
     }
 }
 
 fn css_percentage_parser(input: &mut &str) -> ModalResult<CssPercentage> {
-    terminated(js_float_parser, "%")
-        .map(CssPercentage)
-        .parse_next(input)
+    alt((
+        (|i: &mut &str| calc_parser(None, i)).verify_map(|v| match v {
+            CalcValue::Percentage(p) => Some(CssPercentage(p)),
+            _ => None,
+        }),
+        terminated(
+            number_parser,
+            "%".context(StrContext::Expected(StrContextValue::CharLiteral('%'))),
+        )
+        .map(CssPercentage),
+    ))
+    .context(expected("percentage"))
+    .parse_next(input)
 }
 
 fn css_legacy_num_parser(input: &mut &str) -> ModalResult<CssNum> {
-    (js_float_parser, opt("%"))
-        .map(|(n, p)| {
+    alt((
+        (|i: &mut &str| calc_parser(None, i)).verify_map(CalcValue::into_num),
+        (number_parser, opt("%")).map(|(n, p)| {
             if p.is_some() {
                 CssNum::Percentage(CssPercentage(n))
             } else {
                 CssNum::Num(n)
             }
-        })
-        .parse_next(input)
+        }),
+    ))
+    .context(expected("number or percentage"))
+    .parse_next(input)
 }
 
 fn css_num_parser(input: &mut &str) -> ModalResult<CssNum> {
-    alt((css_legacy_num_parser, "none".map(|_| CssNum::Num(0.)))).parse_next(input)
+    alt((css_legacy_num_parser, "none".map(|_| CssNum::Num(0.))))
+        .context(expected("number, percentage or `none`"))
+        .parse_next(input)
+}
+
+fn angle_to_degrees(n: f32, unit: &str) -> f32 {
+    match unit {
+        "deg" => n,
+        "rad" => n.to_degrees(),
+        "grad" => (n / 400.) * 360.,
+        "turn" => n * 360.,
+        _ => unreachable!(),
+    }
 }
 
 fn css_legacy_hue_parser(input: &mut &str) -> ModalResult<f32> {
-    (js_float_parser, opt(alt(("deg", "rad", "grad", "turn"))))
-        .map(|(n, unit)| {
-            if let Some(unit) = unit {
-                match unit {
-                    "deg" => n,
-                    "rad" => n.to_degrees(),
-                    "grad" => (n / 400.) * 360.,
-                    "turn" => n * 360.,
-                    _ => unreachable!(),
-                }
-            } else {
-                n
-            }
-            .rem_euclid(360.)
-        })
-        .parse_next(input)
+    alt((
+        (|i: &mut &str| calc_parser(None, i)).verify_map(CalcValue::into_degrees),
+        (number_parser, opt(alt(("deg", "rad", "grad", "turn"))))
+            .map(|(n, unit)| unit.map_or(n, |unit| angle_to_degrees(n, unit))),
+    ))
+    .map(|h| h.rem_euclid(360.))
+    .context(expected("hue"))
+    .parse_next(input)
 }
 
 fn css_hue_parser(input: &mut &str) -> ModalResult<f32> {
-    alt((css_legacy_hue_parser, "none".map(|_| 0.))).parse_next(input)
+    alt((css_legacy_hue_parser, "none".map(|_| 0.)))
+        .context(expected("hue or `none`"))
+        .parse_next(input)
 }
 
-fn css_alpha_parser(input: &mut &str) -> ModalResult<f32> {
+fn css_legacy_alpha_parser(input: &mut &str) -> ModalResult<f32> {
     opt(delimited(
-        (space0, '/', space0),
-        css_num_parser.map(|n| n.apply()),
+        comma_parser,
+        cut_err(
+            css_legacy_num_parser
+                .map(|n| n.apply())
+                .context(StrContext::Label("alpha")),
+        ),
         space0,
     ))
     .map(|n| n.unwrap_or(1.))
     .parse_next(input)
 }
 
-fn css_legacy_alpha_parser(input: &mut &str) -> ModalResult<f32> {
-    opt(delimited(
-        (space0, ',', space0),
-        css_legacy_num_parser.map(|n| n.apply()),
-        space0,
+/// Channel keywords of CSS relative color syntax and their values in the origin color
+#[derive(Clone, Copy)]
+struct RelativeChannels {
+    names: [&'static str; 3],
+    values: [f32; 4],
+}
+
+impl RelativeChannels {
+    fn get(&self, keyword: &str) -> Option<f32> {
+        if keyword == "alpha" {
+            return Some(self.values[3]);
+        }
+        self.names
+            .iter()
+            .position(|n| *n == keyword)
+            .map(|i| self.values[i])
+    }
+}
+
+/// The origin color of relative color syntax
+fn relative_origin_parser(input: &mut &str) -> ModalResult<Color> {
+    preceded(("from", space1), css_color_token_parser)
+        .map(|(c, _)| c)
+        .parse_next(input)
+}
+
+/// Any color that can be auto detected as a single CSS token
+fn css_color_token_parser(input: &mut &str) -> ModalResult<(Color, ColorFormat)> {
+    css_token_parser
+        .verify_map(|s: &str| {
+            // A nested mix is only parsed once, trying it with every format would take
+            // exponential time
+            if is_color_mix(s) {
+                return color_mix_parser.parse(s).ok();
+            }
+            UNKNOWN_FORMAT_CANDIDATES
+                .iter()
+                .find_map(|format| parse_color_impl(s, *format).ok().map(|(c, _)| (c, *format)))
+        })
+        .context(expected("color"))
+        .parse_next(input)
+}
+
+/// A function with balanced parentheses or a run of characters without whitespace or commas
+fn css_token_parser<'a>(input: &mut &'a str) -> ModalResult<&'a str> {
+    alt((
+        (
+            take_while(1.., |c: char| c.is_ascii_alphanumeric() || c == '-'),
+            css_parens_parser,
+        )
+            .take(),
+        take_till(1.., |c: char| {
+            c.is_whitespace() || matches!(c, '(' | ')' | ',')
+        }),
     ))
-    .map(|n| n.unwrap_or(1.))
     .parse_next(input)
 }
 
-fn color_read_parser<'a, F1, F2, C: ColorToComponents, E: ParserError<&'a str>>(
-    name: F1,
-    inner: F2,
-) -> impl Parser<&'a str, C, E>
-where
-    F1: Parser<&'a str, (), E>,
-    F2: Parser<&'a str, (f32, f32, f32, f32), E>,
-{
+fn css_parens_parser(input: &mut &str) -> ModalResult<()> {
     delimited(
-        (name, "(", space0),
-        inner.map(|arr| C::from_f32_array([arr.0, arr.1, arr.2, arr.3])),
+        '(',
+        repeat(
+            0..,
+            alt((take_till(1.., ('(', ')')).void(), css_parens_parser)),
+        ),
+        ')',
+    )
+    .parse_next(input)
+}
+
+fn relative_keyword_parser<'a>(
+    origin: RelativeChannels,
+) -> impl Parser<&'a str, f32, ErrMode<ContextError>> {
+    take_while(1.., |c: char| c.is_ascii_alphabetic()).verify_map(move |k: &str| origin.get(k))
+}
+
+/// A `calc()` operand or result. Values can only be added to values of the same unit.
+#[derive(Clone, Copy)]
+enum CalcValue {
+    Number(f32),
+    Percentage(f32),
+    Degrees(f32),
+}
+
+impl CalcValue {
+    fn map(self, f: impl FnOnce(f32) -> f32) -> Self {
+        match self {
+            Self::Number(n) => Self::Number(f(n)),
+            Self::Percentage(n) => Self::Percentage(f(n)),
+            Self::Degrees(n) => Self::Degrees(f(n)),
+        }
+    }
+
+    fn add(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Number(a), Self::Number(b)) => Some(Self::Number(a + b)),
+            (Self::Percentage(a), Self::Percentage(b)) => Some(Self::Percentage(a + b)),
+            (Self::Degrees(a), Self::Degrees(b)) => Some(Self::Degrees(a + b)),
+            _ => None,
+        }
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        match (self, other) {
+            (Self::Number(a), b) => Some(b.map(|b| a * b)),
+            (a, Self::Number(b)) => Some(a.map(|a| a * b)),
+            _ => None,
+        }
+    }
+
+    fn div(self, other: Self) -> Option<Self> {
+        match other {
+            Self::Number(b) if b != 0. => Some(self.map(|a| a / b)),
+            _ => None,
+        }
+    }
+
+    fn into_num(self) -> Option<CssNum> {
+        match self {
+            Self::Number(n) => Some(CssNum::Num(n)),
+            Self::Percentage(n) => Some(CssNum::Percentage(CssPercentage(n))),
+            Self::Degrees(_) => None,
+        }
+    }
+
+    /// Hues accept plain numbers as degrees
+    fn into_degrees(self) -> Option<f32> {
+        match self {
+            Self::Number(n) | Self::Degrees(n) => Some(n),
+            Self::Percentage(_) => None,
+        }
+    }
+}
+
+/// `calc()` with `+ - * /` and parentheses.
+/// Channel keywords can be used as numbers with relative color syntax.
+fn calc_parser(origin: Option<RelativeChannels>, input: &mut &str) -> ModalResult<CalcValue> {
+    delimited(
+        ("calc(", space0),
+        |i: &mut &str| calc_sum_parser(origin, i),
         (space0, ")"),
     )
+    .parse_next(input)
+}
+
+fn calc_sum_parser(origin: Option<RelativeChannels>, input: &mut &str) -> ModalResult<CalcValue> {
+    let mut acc = calc_product_parser(origin, input)?;
+    while let Some(op) = opt(delimited(space0, one_of(['+', '-']), space0)).parse_next(input)? {
+        let rhs = calc_product_parser(origin, input)?;
+        let rhs = if op == '-' { rhs.map(|v| -v) } else { rhs };
+        acc = acc.add(rhs).ok_or_else(|| ErrMode::from_input(input))?;
+    }
+    Ok(acc)
 }
 
-fn oklch_parser(input: &mut &str) -> ModalResult<Oklcha> {
-    color_read_parser(
-        "oklch".void(),
-        (
-            terminated(css_num_parser.map(|n| n.apply()), space1),
-            terminated(css_num_parser.map(|n| n.apply_percent_max(0.4)), space1),
-            css_hue_parser,
-            css_alpha_parser,
-        ),
-    )
-    .parse_next(input)
-}
+fn calc_product_parser(
+    origin: Option<RelativeChannels>,
+    input: &mut &str,
+) -> ModalResult<CalcValue> {
+    let mut acc = calc_value_parser(origin, input)?;
+    while let Some(op) = opt(delimited(space0, one_of(['*', '/']), space0)).parse_next(input)? {
+        let rhs = calc_value_parser(origin, input)?;
+        acc = if op == '*' {
+            acc.mul(rhs)
+        } else {
+            acc.div(rhs)
+        }
+        .ok_or_else(|| ErrMode::from_input(input))?;
+    }
+    Ok(acc)
+}
+
+fn calc_value_parser(origin: Option<RelativeChannels>, input: &mut &str) -> ModalResult<CalcValue> {
+    alt((
+        (number_parser, opt(alt(("%", "deg", "rad", "grad", "turn")))).map(
+            |(n, unit)| match unit {
+                None => CalcValue::Number(n),
+                Some("%") => CalcValue::Percentage(n),
+                Some(unit) => CalcValue::Degrees(angle_to_degrees(n, unit)),
+            },
+        ),
+        |i: &mut &str| calc_parser(origin, i),
+        delimited(
+            ("(", space0),
+            |i: &mut &str| calc_sum_parser(origin, i),
+            (space0, ")"),
+        ),
+        |i: &mut &str| match origin {
+            Some(origin) => relative_keyword_parser(origin)
+                .map(CalcValue::Number)
+                .parse_next(i),
+            None => fail.parse_next(i),
+        },
+    ))
+    .parse_next(input)
+}
+
+/// A channel of a modern CSS color function.
+/// Relative color syntax also allows channel keywords.
+fn css_channel_parser<'a>(
+    origin: Option<RelativeChannels>,
+) -> impl Parser<&'a str, CssNum, ErrMode<ContextError>> {
+    move |input: &mut &'a str| match origin {
+        Some(origin) => alt((
+            (|i: &mut &'a str| calc_parser(Some(origin), i)).verify_map(CalcValue::into_num),
+            relative_keyword_parser(origin).map(CssNum::Num),
+            css_num_parser,
+        ))
+        .parse_next(input),
+        None => css_num_parser.parse_next(input),
+    }
+}
+
+fn css_hue_channel_parser<'a>(
+    origin: Option<RelativeChannels>,
+) -> impl Parser<&'a str, f32, ErrMode<ContextError>> {
+    move |input: &mut &'a str| match origin {
+        Some(origin) => alt((
+            (|i: &mut &'a str| calc_parser(Some(origin), i))
+                .verify_map(CalcValue::into_degrees)
+                .map(|h| h.rem_euclid(360.)),
+            relative_keyword_parser(origin).map(|h| h.rem_euclid(360.)),
+            css_hue_parser,
+        ))
+        .parse_next(input),
+        None => css_hue_parser.parse_next(input),
+    }
+}
+
+/// Alpha defaults to the origin alpha with relative color syntax
+fn css_alpha_channel_parser<'a>(
+    origin: Option<RelativeChannels>,
+) -> impl Parser<&'a str, f32, ErrMode<ContextError>> {
+    opt(delimited(
+        (space0, '/', space0),
+        cut_err(
+            css_channel_parser(origin)
+                .map(|n| n.apply())
+                .context(StrContext::Label("alpha")),
+        ),
+        space0,
+    ))
+    .map(move |n| n.unwrap_or(origin.map_or(1., |o| o.values[3])))
+}
+
+/// A modern CSS color function with optional relative color syntax.
+/// `to_values` converts the origin color to the channels of this function.
+fn css_function_parser<'a, F>(
+    name: &'static str,
+    channels: [&'static str; 3],
+    to_values: fn(LinearRgba) -> [f32; 4],
+    mut inner: impl FnMut(Option<RelativeChannels>) -> F,
+) -> impl Parser<&'a str, (f32, f32, f32, f32), ErrMode<ContextError>>
+where
+    F: Parser<&'a str, (f32, f32, f32, f32), ErrMode<ContextError>>,
+{
+    move |input: &mut &'a str| {
+        css_function_start_parser(name).parse_next(input)?;
+        let origin = opt(terminated(relative_origin_parser, space1))
+            .parse_next(input)?
+            .map(|color| RelativeChannels {
+                names: channels,
+                values: to_values(color.into()),
+            });
+        let values = inner(origin).parse_next(input)?;
+        css_function_end_parser.parse_next(input)?;
+        Ok(values)
+    }
+}
+
+fn css_function_start_parser<'a>(
+    name: &'static str,
+) -> impl Parser<&'a str, (), ErrMode<ContextError>> {
+    (
+        Caseless(name).context(StrContext::Expected(StrContextValue::StringLiteral(name))),
+        "(".context(StrContext::Expected(StrContextValue::CharLiteral('('))),
+        space0,
+    )
+        .void()
+}
+
+fn css_function_end_parser(input: &mut &str) -> ModalResult<()> {
+    (
+        space0,
+        ")".context(StrContext::Expected(StrContextValue::CharLiteral(')'))),
+    )
+        .void()
+        .parse_next(input)
+}
+
+/// A legacy CSS color function, optionally with an `a` suffix like `rgba`
+fn color_read_parser<'a, F, C: ColorToComponents>(
+    name: &'static str,
+    inner: F,
+) -> impl Parser<&'a str, C, ErrMode<ContextError>>
+where
+    F: Parser<&'a str, (f32, f32, f32, f32), ErrMode<ContextError>>,
+{
+    delimited(
+        (
+            Caseless(name).context(StrContext::Expected(StrContextValue::StringLiteral(name))),
+            opt(one_of(['a', 'A'])),
+            "(".context(StrContext::Expected(StrContextValue::CharLiteral('('))),
+            space0,
+        ),
+        inner.map(|arr| C::from_f32_array([arr.0, arr.1, arr.2, arr.3])),
+        css_function_end_parser,
+    )
+}
+
+/// The common `cmyk(c%, m%, y%, k%)` notation, where plain numbers are percentages too
+fn cmyk_parser(input: &mut &str) -> ModalResult<Cmyka> {
+    let ink = |label| {
+        css_legacy_num_parser
+            .map(|n| match n {
+                CssNum::Num(n) => n / 100.,
+                CssNum::Percentage(p) => p.apply(),
+            })
+            .context(StrContext::Label(label))
+    };
+    delimited(
+        css_function_start_parser("cmyk"),
+        (
+            terminated(ink("cyan"), comma_parser),
+            terminated(ink("magenta"), comma_parser),
+            terminated(ink("yellow"), comma_parser),
+            ink("black"),
+        ),
+        css_function_end_parser,
+    )
+    .map(|(c, m, y, k)| Cmyka::new(c, m, y, k, 1.))
+    .parse_next(input)
+}
+
+/// CSS `device-cmyk()` with space separated channels and optional `/ alpha`, or the legacy
+/// comma separated form
+fn device_cmyk_parser(input: &mut &str) -> ModalResult<Cmyka> {
+    let ink = |label| {
+        css_num_parser
+            .map(|n| n.apply())
+            .context(StrContext::Label(label))
+    };
+    let legacy_ink = |label| {
+        css_legacy_num_parser
+            .map(|n| n.apply())
+            .context(StrContext::Label(label))
+    };
+    delimited(
+        css_function_start_parser("device-cmyk"),
+        alt((
+            (
+                terminated(legacy_ink("cyan"), comma_parser),
+                terminated(legacy_ink("magenta"), comma_parser),
+                terminated(legacy_ink("yellow"), comma_parser),
+                legacy_ink("black"),
+                css_legacy_alpha_parser,
+            ),
+            (
+                terminated(ink("cyan"), space_parser),
+                terminated(ink("magenta"), space_parser),
+                terminated(ink("yellow"), space_parser),
+                ink("black"),
+                css_alpha_channel_parser(None),
+            ),
+        )),
+        css_function_end_parser,
+    )
+    .map(|(c, m, y, k, a)| Cmyka::new(c, m, y, k, a))
+    .parse_next(input)
+}
+
+/// Four inks from 0 to 100 and optionally alpha from 0 to 1
+fn raw_cmyk_parser(input: &mut &str) -> ModalResult<(Cmyka, bool)> {
+    separated(4..=5, number_parser, comma_parser)
+        .verify_map(|values: Vec<f32>| {
+            let inks = values[..4].iter().all(|v| (0. ..=100.).contains(v));
+            let alpha = values.get(4).is_none_or(|a| (0. ..=1.).contains(a));
+            (inks && alpha).then(|| {
+                let [c, m, y, k] = [0, 1, 2, 3].map(|i| values[i] / 100.);
+                let a = values.get(4).copied();
+                (Cmyka::new(c, m, y, k, a.unwrap_or(1.)), a.is_some())
+            })
+        })
+        .context(expected("4 numbers from 0 to 100 and optional alpha"))
+        .parse_next(input)
+}
+
+fn oklch_parser(input: &mut &str) -> ModalResult<Oklcha> {
+    css_function_parser(
+        "oklch",
+        ["l", "c", "h"],
+        |c| Oklcha::from(c).to_f32_array(),
+        |origin| {
+            (
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply())
+                        .context(StrContext::Label("oklch lightness")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(0.4))
+                        .context(StrContext::Label("oklch chroma")),
+                    space_parser,
+                ),
+                css_hue_channel_parser(origin).context(StrContext::Label("oklch hue")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(l, c, h, a)| Oklcha::new(l, c, h, a))
+    .parse_next(input)
+}
+
+fn oklab_parser(input: &mut &str) -> ModalResult<Oklaba> {
+    css_function_parser(
+        "oklab",
+        ["l", "a", "b"],
+        |c| Oklaba::from(c).to_f32_array(),
+        |origin| {
+            (
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply())
+                        .context(StrContext::Label("oklab lightness")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(0.4))
+                        .context(StrContext::Label("oklab a")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.apply_percent_max(0.4))
+                    .context(StrContext::Label("oklab b")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(l, a, b, alpha)| Oklaba::new(l, a, b, alpha))
+    .parse_next(input)
+}
+
+fn rgb_parser(input: &mut &str) -> ModalResult<Srgba> {
+    css_function_parser(
+        "rgb",
+        ["r", "g", "b"],
+        |c| {
+            let [r, g, b, a] = Srgba::from(c).to_f32_array();
+            [r * 255., g * 255., b * 255., a]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.as_u8())
+                        .context(StrContext::Label("rgb red")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.as_u8())
+                        .context(StrContext::Label("rgb green")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.as_u8())
+                    .context(StrContext::Label("rgb blue")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(r, g, b, a)| Srgba::new(r, g, b, a))
+    .parse_next(input)
+}
+
+fn hsl_parser(input: &mut &str) -> ModalResult<Hsla> {
+    css_function_parser(
+        "hsl",
+        ["h", "s", "l"],
+        |c| {
+            let c = Hsla::from(c);
+            [c.hue, c.saturation * 100., c.lightness * 100., c.alpha]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_hue_channel_parser(origin).context(StrContext::Label("hsl hue")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(100.) / 100.)
+                        .context(StrContext::Label("hsl saturation")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.apply_percent_max(100.) / 100.)
+                    .context(StrContext::Label("hsl lightness")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(h, s, l, a)| Hsla::new(h, s, l, a))
+    .parse_next(input)
+}
+
+fn okhsv_parser(input: &mut &str) -> ModalResult<Okhsva> {
+    css_function_parser(
+        "okhsv",
+        ["h", "s", "v"],
+        |c| {
+            let c = Okhsva::from(Oklaba::from(c));
+            [c.hue, c.saturation, c.value, c.alpha]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_hue_channel_parser(origin).context(StrContext::Label("okhsv hue")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply())
+                        .context(StrContext::Label("okhsv saturation")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.apply())
+                    .context(StrContext::Label("okhsv value")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(h, s, v, a)| Okhsva::new(h, s, v, a))
+    .parse_next(input)
+}
+
+fn okhsl_parser(input: &mut &str) -> ModalResult<Okhsla> {
+    css_function_parser(
+        "okhsl",
+        ["h", "s", "l"],
+        |c| {
+            let c = Okhsla::from(Oklaba::from(c));
+            [c.hue, c.saturation, c.lightness, c.alpha]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_hue_channel_parser(origin).context(StrContext::Label("okhsl hue")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply())
+                        .context(StrContext::Label("okhsl saturation")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.apply())
+                    .context(StrContext::Label("okhsl lightness")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(h, s, l, a)| Okhsla::new(h, s, l, a))
+    .parse_next(input)
+}
+
+/// Three numbers and optionally alpha, returns whether there was alpha
+fn raw_values_parser(input: &mut &str) -> ModalResult<([f32; 4], bool)> {
+    separated(3..=4, number_parser, comma_parser)
+        .map(|values: Vec<f32>| {
+            let alpha = values.get(3).copied();
+            (
+                [values[0], values[1], values[2], alpha.unwrap_or(1.)],
+                alpha.is_some(),
+            )
+        })
+        .parse_next(input)
+}
+
+/// Hue in degrees, then two channels and optionally alpha from 0 to 1
+fn raw_okhs_parser(input: &mut &str) -> ModalResult<([f32; 4], bool)> {
+    separated(3..=4, number_parser, comma_parser)
+        .verify_map(|values: Vec<f32>| {
+            let unit = |v: &f32| (0. ..=1.).contains(v);
+            ((0. ..=360.).contains(&values[0]) && values[1..].iter().all(unit)).then(|| {
+                let alpha = values.get(3).copied();
+                (
+                    [values[0], values[1], values[2], alpha.unwrap_or(1.)],
+                    alpha.is_some(),
+                )
+            })
+        })
+        .context(expected("hue from 0 to 360 and numbers from 0 to 1"))
+        .parse_next(input)
+}
+
+fn hwb_parser(input: &mut &str) -> ModalResult<Hwba> {
+    css_function_parser(
+        "hwb",
+        ["h", "w", "b"],
+        |c| {
+            let c = Hwba::from(c);
+            [c.hue, c.whiteness * 100., c.blackness * 100., c.alpha]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_hue_channel_parser(origin).context(StrContext::Label("hwb hue")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(100.) / 100.)
+                        .context(StrContext::Label("hwb whiteness")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.apply_percent_max(100.) / 100.)
+                    .context(StrContext::Label("hwb blackness")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(h, w, b, a)| Hwba::new(h, w, b, a))
+    .parse_next(input)
+}
+
+fn lab_parser(input: &mut &str) -> ModalResult<CieLaba> {
+    css_function_parser(
+        "lab",
+        ["l", "a", "b"],
+        |c| {
+            let c = CieLaba::from(c);
+            [c.lightness, c.a, c.b, c.alpha]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(100.))
+                        .context(StrContext::Label("lab lightness")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(125.))
+                        .context(StrContext::Label("lab a")),
+                    space_parser,
+                ),
+                css_channel_parser(origin)
+                    .map(|n| n.apply_percent_max(125.))
+                    .context(StrContext::Label("lab b")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(l, a, b, alpha)| CieLaba::new(l, a, b, alpha))
+    .parse_next(input)
+}
+
+fn lch_parser(input: &mut &str) -> ModalResult<CieLcha> {
+    css_function_parser(
+        "lch",
+        ["l", "c", "h"],
+        |c| {
+            let c = CieLcha::from(c);
+            [c.lightness, c.chroma, c.hue, c.alpha]
+        },
+        |origin| {
+            (
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(100.))
+                        .context(StrContext::Label("lch lightness")),
+                    space_parser,
+                ),
+                terminated(
+                    css_channel_parser(origin)
+                        .map(|n| n.apply_percent_max(150.))
+                        .context(StrContext::Label("lch chroma")),
+                    space_parser,
+                ),
+                css_hue_channel_parser(origin).context(StrContext::Label("lch hue")),
+                css_alpha_channel_parser(origin),
+            )
+        },
+    )
+    .map(|(l, c, h, alpha)| CieLcha::new(l, c, h, alpha))
+    .parse_next(input)
+}
+
+/// `color()` with a predefined color space, which must be `expected_space`
+fn css_color_function_parser<'a>(
+    expected_space: CssColorSpace,
+) -> impl Parser<&'a str, [f32; 4], ErrMode<ContextError>> {
+    move |input: &mut &'a str| {
+        css_function_start_parser("color").parse_next(input)?;
+        let origin = opt(terminated(relative_origin_parser, space1)).parse_next(input)?;
+        let space: CssColorSpace = terminated(
+            take_while(1.., ('a'..='z', '0'..='9', '-'))
+                .parse_to()
+                .verify(|space| *space == expected_space)
+                .context(StrContext::Expected(StrContextValue::StringLiteral(
+                    expected_space.into(),
+                ))),
+            space_parser,
+        )
+        .parse_next(input)?;
+        let origin = origin.map(|color| RelativeChannels {
+            names: match space {
+                CssColorSpace::XyzD50 | CssColorSpace::XyzD65 => ["x", "y", "z"],
+                _ => ["r", "g", "b"],
+            },
+            values: space.to_values(color.into()),
+        });
+        let (c1, c2, c3, alpha) = (
+            terminated(
+                css_channel_parser(origin)
+                    .map(|n| n.apply())
+                    .context(StrContext::Label("color() first channel")),
+                space_parser,
+            ),
+            terminated(
+                css_channel_parser(origin)
+                    .map(|n| n.apply())
+                    .context(StrContext::Label("color() second channel")),
+                space_parser,
+            ),
+            css_channel_parser(origin)
+                .map(|n| n.apply())
+                .context(StrContext::Label("color() third channel")),
+            css_alpha_channel_parser(origin),
+        )
+            .parse_next(input)?;
+        css_function_end_parser.parse_next(input)?;
+        Ok([c1, c2, c3, alpha])
+    }
+}
+
+/// A color and its optional percentage in either order
+fn color_mix_operand_parser(input: &mut &str) -> ModalResult<(Color, Option<f32>)> {
+    // The color comes last so a bad color is what gets reported
+    alt((
+        (
+            terminated(css_percentage_parser.map(|p| p.apply()), space1),
+            css_color_token_parser.map(|(c, _)| c),
+        )
+            .map(|(p, c)| (c, Some(p))),
+        (
+            css_color_token_parser.map(|(c, _)| c),
+            opt(preceded(space1, css_percentage_parser.map(|p| p.apply()))),
+        ),
+    ))
+    .parse_next(input)
+}
+
+/// Evaluates `color-mix()`. The format is the function matching the interpolation space.
+fn color_mix_parser(input: &mut &str) -> ModalResult<(Color, ColorFormat)> {
+    let (space, hue_interpolation, (c1, p1), (c2, p2)) = delimited(
+        (
+            Caseless("color-mix("),
+            space0,
+            "in".context(StrContext::Expected(StrContextValue::StringLiteral("in"))),
+            space_parser,
+        ),
+        (
+            take_while(1.., ('a'..='z', '0'..='9', '-'))
+                .verify_map(MixSpace::from_name)
+                .context(expected("interpolation color space")),
+            opt(delimited(
+                space1,
+                take_while(1.., 'a'..='z').parse_to::<HueInterpolation>(),
+                (space1, "hue"),
+            ))
+            .map(Option::unwrap_or_default),
+            preceded(comma_parser, color_mix_operand_parser),
+            preceded(comma_parser, color_mix_operand_parser),
+        ),
+        css_function_end_parser,
+    )
+    .parse_next(input)?;
+
+    // Normalize percentages to sum to 100%. A smaller sum makes the result transparent.
+    let (p1, p2) = match (p1, p2) {
+        (None, None) => (0.5, 0.5),
+        (Some(p1), None) => (p1, 1. - p1),
+        (None, Some(p2)) => (1. - p2, p2),
+        (Some(p1), Some(p2)) => (p1, p2),
+    };
+    let sum = p1 + p2;
+    if !(0. ..=1.).contains(&p1) || !(0. ..=1.).contains(&p2) || sum <= 0. {
+        return fail
+            .context(expected("percentages from 0% to 100% with a positive sum"))
+            .parse_next(input);
+    }
+
+    let mut color = mix(space, hue_interpolation, c1.into(), c2.into(), p2 / sum);
+    color.alpha *= sum.min(1.);
+
+    use ColorFormat as F;
+    use CssColorSpace as S;
+    let format = match space {
+        MixSpace::Rectangular(S::Srgb) => F::ColorSrgb,
+        MixSpace::Rectangular(S::SrgbLinear) => F::ColorSrgbLinear,
+        MixSpace::Rectangular(S::DisplayP3) => F::ColorDisplayP3,
+        MixSpace::Rectangular(S::A98Rgb) => F::ColorA98Rgb,
+        MixSpace::Rectangular(S::ProphotoRgb) => F::ColorProphotoRgb,
+        MixSpace::Rectangular(S::Rec2020) => F::ColorRec2020,
+        MixSpace::Rectangular(S::XyzD50) => F::ColorXyzD50,
+        MixSpace::Rectangular(S::XyzD65) => F::ColorXyzD65,
+        MixSpace::Lab => F::Lab,
+        MixSpace::Oklab => F::Oklab,
+        MixSpace::Hsl => F::Hsl,
+        MixSpace::Hwb => F::Hwb,
+        MixSpace::Lch => F::Lch,
+        MixSpace::Oklch => F::Oklch,
+    };
+
+    Ok((color.into(), format))
+}
+
+/// A number with an optional `f` suffix, like float literals in shaders and C#
+fn float_component_parser(input: &mut &str) -> ModalResult<f32> {
+    terminated(number_parser, opt(one_of(['f', 'F']))).parse_next(input)
+}
+
+/// An integer from 0 to 255, scaled to 0 to 1
+fn byte_component_parser(input: &mut &str) -> ModalResult<f32> {
+    u8_parser.map(|c| c as f32 / 255.).parse_next(input)
+}
+
+fn u8_parser(input: &mut &str) -> ModalResult<u8> {
+    digit1
+        .try_map(|s: &str| s.parse::<u8>())
+        .context(expected("integer from 0 to 255"))
+        .parse_next(input)
+}
+
+/// A call like `vec3(0.1, 0.2, 0.3)` or `new Color(0.1f, 0.2f, 0.3f, 1f)`.
+/// `rgb` and `rgba` are the names taking 3 and 4 components. With `splat`, a single
+/// value is used for all components. Returns whether there was alpha.
+fn call_parser<'a>(
+    rgb: &'static [&'static str],
+    rgba: &'static [&'static str],
+    splat: bool,
+    component: fn(&mut &'a str) -> ModalResult<f32>,
+) -> impl Parser<&'a str, ([f32; 4], bool), ErrMode<ContextError>> {
+    let name = rgb.first().or(rgba.first()).copied().unwrap_or_default();
+    move |input: &mut &'a str| {
+        let (three, four) = (
+            opt(("new", space1)),
+            take_while(1.., |c: char| {
+                c.is_ascii_alphanumeric() || matches!(c, '_' | ':' | '.' | '<' | '>')
+            }),
+        )
+            .take()
+            .verify_map(|s: &str| {
+                let matches = |n: &&str| s.split_whitespace().eq(n.split_whitespace());
+                let lens = (rgb.iter().any(matches), rgba.iter().any(matches));
+                (lens != (false, false)).then_some(lens)
+            })
+            .context(StrContext::Expected(StrContextValue::StringLiteral(name)))
+            .parse_next(input)?;
+
+        delimited(
+            (space0, '(', space0),
+            separated(1..=4, component, comma_parser),
+            (space0, ')'),
+        )
+        .verify_map(|values: Vec<f32>| match values[..] {
+            [v] if splat && four => Some(([v; 4], true)),
+            [v] if splat => Some(([v, v, v, 1.], false)),
+            [r, g, b] if three => Some(([r, g, b, 1.], false)),
+            [r, g, b, a] if four => Some(([r, g, b, a], true)),
+            _ => None,
+        })
+        .context(expected(match (three, four) {
+            (true, true) => "3 or 4 components",
+            (true, false) => "3 components",
+            _ => "4 components",
+        }))
+        .parse_next(input)
+    }
+}
+
+/// A call with labelled arguments like `UIColor(red: 1, green: 0.5, blue: 0, alpha: 1)`.
+/// Returns whether there was alpha.
+fn labelled_call_parser<'a>(
+    name: &'static str,
+    labels: [&'static str; 4],
+    assign: char,
+    alpha_required: bool,
+) -> impl Parser<&'a str, ([f32; 4], bool), ErrMode<ContextError>> {
+    move |input: &mut &'a str| {
+        let arg = |label: &'static str| {
+            preceded(
+                (
+                    label.context(StrContext::Expected(StrContextValue::StringLiteral(label))),
+                    space0,
+                    assign.context(StrContext::Expected(StrContextValue::CharLiteral(assign))),
+                    space0,
+                ),
+                cut_err(float_component_parser.context(StrContext::Label(label))),
+            )
+        };
+        let (r, _, g, _, b) = preceded(
+            (
+                name.context(StrContext::Expected(StrContextValue::StringLiteral(name))),
+                space0,
+                '(',
+                space0,
+            ),
+            (
+                arg(labels[0]),
+                comma_parser,
+                arg(labels[1]),
+                comma_parser,
+                arg(labels[2]),
+            ),
+        )
+        .parse_next(input)?;
+        let a = opt(preceded(comma_parser, arg(labels[3])))
+            .verify(|a| !alpha_required || a.is_some())
+            .context(StrContext::Expected(StrContextValue::StringLiteral(
+                labels[3],
+            )))
+            .parse_next(input)?;
+        (space0, ')').parse_next(input)?;
+        Ok(([r, g, b, a.unwrap_or(1.)], a.is_some()))
+    }
+}
+
+/// An ARGB hex literal call like `Color(0xFFFF0000)`
+fn argb_hex_call_parser(input: &mut &str) -> ModalResult<[f32; 4]> {
+    delimited(
+        (
+            "Color".context(StrContext::Expected(StrContextValue::StringLiteral(
+                "Color",
+            ))),
+            space0,
+            '(',
+            space0,
+            "0x".context(StrContext::Expected(StrContextValue::StringLiteral("0x"))),
+        ),
+        take_while(8, |c: char| c.is_ascii_hexdigit()).context(expected("8 hex digits")),
+        (space0, ')'),
+    )
+    .verify_map(|hex| {
+        let mut values = parse_hex(hex, false)?.0.to_f32_array();
+        values.rotate_left(1);
+        Some(values)
+    })
+    .parse_next(input)
+}
+
+/// Flutter's `Color.fromRGBO(r, g, b, opacity)` with integer channels and float opacity
+fn flutter_rgbo_parser(input: &mut &str) -> ModalResult<[f32; 4]> {
+    delimited(
+        (
+            "Color.fromRGBO".context(StrContext::Expected(StrContextValue::StringLiteral(
+                "Color.fromRGBO",
+            ))),
+            space0,
+            '(',
+            space0,
+        ),
+        (
+            byte_component_parser,
+            comma_parser,
+            byte_component_parser,
+            comma_parser,
+            byte_component_parser,
+            comma_parser,
+            number_parser.context(StrContext::Label("opacity")),
+        ),
+        (space0, ')'),
+    )
+    .map(|(r, _, g, _, b, _, a)| [r, g, b, a])
+    .parse_next(input)
+}
+
+/// The XParseColor `rgb:r/g/b` form, with 1 to 4 hex digits per channel
+fn x11_rgb_parser(input: &mut &str) -> ModalResult<Srgba> {
+    let channel = || {
+        take_while(1..=4, |c: char| c.is_ascii_hexdigit())
+            .try_map(|s: &str| {
+                let max = ((1 << (4 * s.len())) - 1) as f32;
+                u16::from_str_radix(s, 16).map(|c| c as f32 / max)
+            })
+            .context(expected("1 to 4 hex digits"))
+    };
+    preceded(
+        Caseless("rgb:").context(StrContext::Expected(StrContextValue::StringLiteral("rgb:"))),
+        (channel(), '/', channel(), '/', channel()),
+    )
+    .map(|(r, _, g, _, b)| Srgba::rgb(r, g, b))
+    .parse_next(input)
+}
+
+/// The XParseColor `rgbi:r/g/b` form with intensities from 0 to 1. Without a display
+/// profile these are the same as sRGB values, which is how terminals read them.
+fn x11_rgbi_parser(input: &mut &str) -> ModalResult<Srgba> {
+    let channel = || {
+        number_parser
+            .verify(|c| (0. ..=1.).contains(c))
+            .context(expected("number from 0 to 1"))
+    };
+    preceded(
+        Caseless("rgbi:").context(StrContext::Expected(StrContextValue::StringLiteral(
+            "rgbi:",
+        ))),
+        (channel(), '/', channel(), '/', channel()),
+    )
+    .map(|(r, _, g, _, b)| Srgba::rgb(r, g, b))
+    .parse_next(input)
+}
+
+/// The escape character as written in shells and source code, or the character itself
+fn escape_parser(input: &mut &str) -> ModalResult<()> {
+    alt((
+        "\x1b",
+        "\\e",
+        Caseless("\\x1b"),
+        "\\033",
+        Caseless("\\u001b"),
+        Caseless("\\u{1b}"),
+    ))
+    .void()
+    .parse_next(input)
+}
+
+/// The start of an SGR color like `\e[38;2;` or `48;5;`, `kind` is `2` for RGB colors and
+/// `5` for palette indices. Returns whether there was an escape, which needs a final `m`.
+fn sgr_prefix_parser<'a>(kind: char) -> impl Parser<&'a str, bool, ErrMode<ContextError>> {
+    (opt((escape_parser, '[')), alt(("38", "48")), ';', kind, ';')
+        .map(|(escape, ..)| escape.is_some())
+        .context(StrContext::Expected(StrContextValue::Description(
+            if kind == '2' {
+                "`38;2;` or `48;2;`"
+            } else {
+                "`38;5;` or `48;5;`"
+            },
+        )))
+}
+
+/// An ANSI truecolor escape like `\e[38;2;255;0;0m`, or just its parameters `38;2;255;0;0`
+fn ansi_truecolor_parser(input: &mut &str) -> ModalResult<Srgba> {
+    let escaped = sgr_prefix_parser('2').parse_next(input)?;
+    let (r, _, g, _, b) = (u8_parser, ';', u8_parser, ';', u8_parser).parse_next(input)?;
+    if escaped {
+        'm'.context(StrContext::Expected(StrContextValue::CharLiteral('m')))
+            .parse_next(input)?;
+    }
+    Ok(Srgba::rgb_u8(r, g, b))
+}
+
+/// An xterm-256 index, optionally as an escape like `\e[38;5;196m` or as tmux `colour196`
+fn ansi256_parser(input: &mut &str) -> ModalResult<u8> {
+    let escaped = opt(alt((
+        sgr_prefix_parser('5'),
+        alt((Caseless("colour"), Caseless("color"))).value(false),
+    )))
+    .parse_next(input)?;
+    let index = u8_parser.parse_next(input)?;
+    if escaped == Some(true) {
+        'm'.context(StrContext::Expected(StrContextValue::CharLiteral('m')))
+            .parse_next(input)?;
+    }
+    Ok(index)
+}
+
+/// A call with a hex string like `Color("#ff0000")`, returns the hex digits
+fn hex_string_call_parser<'a>(
+    name: &'static str,
+) -> impl Parser<&'a str, &'a str, ErrMode<ContextError>> {
+    delimited(
+        (
+            name.context(StrContext::Expected(StrContextValue::StringLiteral(name))),
+            space0,
+            '(',
+            space0,
+            '"'.context(StrContext::Expected(StrContextValue::CharLiteral('"'))),
+            opt('#'),
+        ),
+        take_while(1.., |c: char| c.is_ascii_hexdigit()).context(expected("hex digits")),
+        (
+            '"'.context(StrContext::Expected(StrContextValue::CharLiteral('"'))),
+            space0,
+            ')',
+        ),
+    )
+}
+
+/// Color models of the LaTeX xcolor formats
+#[cfg(not(target_arch = "wasm32"))]
+const LATEX_MODELS: [&str; 3] = ["HTML", "rgb", "RGB"];
+
+/// An xcolor color like `\definecolor{name}{HTML}{AABBCC}`, `\color[rgb]{0.1,0.2,0.3}` or
+/// just `{RGB}{10,20,30}`, with `values` parsing the last group
+fn latex_color_parser<'a, O>(
+    model: &'static str,
+    values: impl Parser<&'a str, O, ErrMode<ContextError>>,
+) -> impl Parser<&'a str, O, ErrMode<ContextError>> {
+    let model_group = move |input: &mut &'a str| {
+        alt((delimited('[', model, ']'), delimited('{', model, '}')))
+            .void()
+            .parse_next(input)
+    };
+    preceded(
+        (
+            opt(('\\', take_while(1.., |c: char| c.is_ascii_alphabetic()))),
+            alt((
+                model_group,
+                (delimited('{', take_till(0.., '}'), '}'), model_group).void(),
+            )),
+        )
+            .context(StrContext::Expected(StrContextValue::StringLiteral(model))),
+        delimited(
+            ('{', space0),
+            values,
+            (
+                space0,
+                '}'.context(StrContext::Expected(StrContextValue::CharLiteral('}'))),
+            ),
+        ),
+    )
+}
+
+/// Typst `oklch(50%, 0.1, 120deg)`, where alpha is an optional fourth argument
+fn typst_oklch_parser(input: &mut &str) -> ModalResult<Oklcha> {
+    delimited(
+        css_function_start_parser("oklch"),
+        (
+            terminated(
+                css_legacy_num_parser
+                    .map(|n| n.apply())
+                    .context(StrContext::Label("oklch lightness")),
+                comma_parser,
+            ),
+            terminated(
+                css_legacy_num_parser
+                    .map(|n| n.apply_percent_max(0.4))
+                    .context(StrContext::Label("oklch chroma")),
+                comma_parser,
+            ),
+            css_legacy_hue_parser.context(StrContext::Label("oklch hue")),
+            css_legacy_alpha_parser,
+        ),
+        css_function_end_parser,
+    )
+    .map(|(l, c, h, a)| Oklcha::new(l, c, h, a))
+    .parse_next(input)
+}
+
+/// Typst `luma(128)` or `luma(50%)`, a gray with the sRGB transfer function
+fn typst_luma_parser(input: &mut &str) -> ModalResult<Srgba> {
+    delimited(
+        css_function_start_parser("luma"),
+        (
+            alt((
+                terminated(number_parser, "%").map(|n| n / 100.),
+                u8_parser.map(|n| n as f32 / 255.),
+            ))
+            .context(StrContext::Label("luma lightness")),
+            css_legacy_alpha_parser,
+        ),
+        css_function_end_parser,
+    )
+    .map(|(l, a)| Srgba::new(l, l, l, a))
+    .parse_next(input)
+}
+
+fn rgb_legacy_parser(input: &mut &str) -> ModalResult<Srgba> {
+    color_read_parser(
+        "rgb",
+        (
+            terminated(
+                css_legacy_num_parser.context(StrContext::Label("rgb red")),
+                comma_parser,
+            ),
+            terminated(
+                css_legacy_num_parser.context(StrContext::Label("rgb green")),
+                comma_parser,
+            ),
+            css_legacy_num_parser.context(StrContext::Label("rgb blue")),
+            css_legacy_alpha_parser,
+        )
+            .verify(|(r, g, b, _)| {
+                matches!(
+                    (r, g, b),
+                    (
+                        CssNum::Percentage(_),
+                        CssNum::Percentage(_),
+                        CssNum::Percentage(_)
+                    ) | (CssNum::Num(_), CssNum::Num(_), CssNum::Num(_))
+                )
+            })
+            .context(expected("all numbers or all percentages"))
+            .map(|(r, g, b, a)| (r.as_u8(), g.as_u8(), b.as_u8(), a)),
+    )
+    .parse_next(input)
+}
+
+fn hsl_legacy_parser(input: &mut &str) -> ModalResult<Hsla> {
+    color_read_parser(
+        "hsl",
+        (
+            terminated(
+                css_legacy_hue_parser.context(StrContext::Label("hsl hue")),
+                comma_parser,
+            ),
+            terminated(
+                css_percentage_parser
+                    .map(|p| p.apply())
+                    .context(StrContext::Label("hsl saturation")),
+                comma_parser,
+            ),
+            css_percentage_parser
+                .map(|p| p.apply())
+                .context(StrContext::Label("hsl lightness")),
+            css_legacy_alpha_parser,
+        ),
+    )
+    .parse_next(input)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn number1() {
+        let res = number_parser.parse("1.0");
+        assert_eq!(res, Ok(1.0));
+    }
+
+    #[test]
+    fn number2() {
+        let res = number_parser.parse("1");
+        assert_eq!(res, Ok(1.0));
+    }
+
+    #[test]
+    fn number3() {
+        let res = number_parser.parse(".1");
+        assert_eq!(res, Ok(0.1));
+    }
+
+    #[test]
+    fn fail_number1() {
+        let res = number_parser.parse(" 1.0");
+        assert!(matches!(res, Err(..)))
+    }
+
+    #[test]
+    fn fail_number2() {
+        let res = number_parser.parse("1.0 ");
+        assert!(matches!(res, Err(..)))
+    }
+
+    fn restyle(s: &str, format: ColorFormat) -> String {
+        let (c, use_alpha) = parse_color(s, format).unwrap();
+        let style = ColorStyle::detect(s, format);
+        format_color_styled(c.into(), format, use_alpha, &style, &Default::default())
+    }
+
+    fn to_hex(s: &str, format: ColorFormat) -> (String, bool) {
+        let (c, use_alpha) = parse_color(s, format).unwrap();
+        (
+            format_color(c.into(), ColorFormat::Hex, use_alpha),
+            use_alpha,
+        )
+    }
+
+    #[test]
+    fn style_roundtrip() {
+        for (s, format) in [
+            ("#AABBCC", ColorFormat::Hex),
+            ("#aabbcc", ColorFormat::Hex),
+            ("#ABC", ColorFormat::Hex),
+            ("0xaabbcc", ColorFormat::HexLiteral),
+            ("RGB(10,20,30)", ColorFormat::RgbLegacy),
+            ("rgba( 10 , 20 , 30 , 0.5 )", ColorFormat::RgbLegacy),
+            ("rgb(100% 0% 50%/50%)", ColorFormat::Rgb),
+            ("hsl(0.5turn 50% 25%)", ColorFormat::Hsl),
+            ("HSLA(90deg, 50%, 25%, 1)", ColorFormat::HslLegacy),
+            ("oklch(0.5 25% 120)", ColorFormat::Oklch),
+            ("color(srgb 100% 0% 0% / 0.5)", ColorFormat::ColorSrgb),
+            ("10,20,30", ColorFormat::RawRgb),
+        ] {
+            assert_eq!(restyle(s, format), s);
+        }
+    }
+
+    #[test]
+    fn style_relative() {
+        assert_eq!(
+            restyle("RGB( from #102030 r g b )", ColorFormat::Rgb),
+            "RGB( 16 32 48 )"
+        );
+    }
+
+    #[test]
+    fn style_default() {
+        let style = ColorStyle::detect("rgb(calc(10 + 10) none 30)", ColorFormat::Rgb);
+        assert_eq!(style.channels, [None, None, Some(NumberStyle::Number)]);
+        assert_eq!(
+            ColorStyle::detect("#123", ColorFormat::Hex).uppercase_hex,
+            None
+        );
+    }
+
+    fn format_with(s: &str, format: ColorFormat, options: FormatOptions) -> String {
+        let (c, use_alpha) = parse_color(s, format).unwrap();
+        let style = ColorStyle::detect(s, format);
+        format_color_styled(c.into(), format, use_alpha, &style, &options)
+    }
+
+    #[test]
+    fn options_precision() {
+        let options = FormatOptions {
+            precision: [Some(0), Some(2), Some(0), Some(2)],
+            ..Default::default()
+        };
+        assert_eq!(
+            format_with(
+                "oklch(54.3% 0.1234 40.9 / 50%)",
+                ColorFormat::Oklch,
+                options
+            ),
+            "oklch(54% 0.12 41 / 50%)"
+        );
+        assert_eq!(
+            format_with("0.1234, 0.5, 1, 0.25", ColorFormat::RawRgbFloat, options),
+            "0.0, 0.5, 1.0, 0.25"
+        );
+    }
+
+    #[test]
+    fn options_hex() {
+        let options = FormatOptions {
+            uppercase_hex: Some(true),
+            short_hex: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(format_with("#abc", ColorFormat::Hex, options), "#AABBCC");
+        let options = FormatOptions {
+            uppercase_hex: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_with("0xAABBCC", ColorFormat::HexLiteral, options),
+            "0xaabbcc"
+        );
+    }
+
+    #[test]
+    fn options_styles() {
+        let options = FormatOptions {
+            hue_unit: Some(HueUnit::Turn),
+            lightness: Some(NumberStyle::Number),
+            alpha: Some(NumberStyle::Number),
+            ..Default::default()
+        };
+        assert_eq!(
+            format_with("oklch(50% 0.1 90 / 50%)", ColorFormat::Oklch, options),
+            "oklch(0.5 0.1 0.25turn / 0.5)"
+        );
+        assert_eq!(
+            format_with("hsl(180 50% 50%)", ColorFormat::Hsl, options),
+            "hsl(0.5turn 50% 50)"
+        );
+    }
+
+    #[test]
+    fn options_alpha_mode() {
+        let always = FormatOptions {
+            alpha_mode: Some(AlphaMode::Always),
+            ..Default::default()
+        };
+        let never = FormatOptions {
+            alpha_mode: Some(AlphaMode::Never),
+            ..Default::default()
+        };
+        assert_eq!(format_with("#abc", ColorFormat::Hex, always), "#abcf");
+        assert_eq!(format_with("#abc8", ColorFormat::Hex, never), "#abc");
+        assert_eq!(
+            format_with("rgb(1 2 3)", ColorFormat::Rgb, always),
+            "rgb(1 2 3 / 100%)"
+        );
+        assert_eq!(
+            format_with("rgb(1 2 3 / 0.5)", ColorFormat::Rgb, never),
+            "rgb(1 2 3)"
+        );
+        assert_eq!(
+            format_with("1, 2, 3", ColorFormat::RawRgb, always),
+            "1, 2, 3, 255"
+        );
+    }
+
+    #[test]
+    fn options_or() {
+        let a = FormatOptions {
+            precision: [Some(1), None, None, None],
+            hue_unit: Some(HueUnit::Deg),
+            ..Default::default()
+        };
+        let b = FormatOptions {
+            precision: [Some(2), Some(3), None, None],
+            hue_unit: Some(HueUnit::Rad),
+            uppercase_hex: Some(true),
+            ..Default::default()
+        };
+        let merged = a.or(b);
+        assert_eq!(merged.precision, [Some(1), Some(3), None, None]);
+        assert_eq!(merged.hue_unit, Some(HueUnit::Deg));
+        assert_eq!(merged.uppercase_hex, Some(true));
+    }
+
+    #[test]
+    fn number_grammar() {
+        assert_eq!(number_parser.parse("-1.5e2"), Ok(-150.));
+        assert_eq!(number_parser.parse("+.5"), Ok(0.5));
+        assert_eq!(number_parser.parse("1e-3"), Ok(0.001));
+        assert_eq!(number_parser.parse("2."), Ok(2.));
+    }
+
+    #[test]
+    fn fail_number_grammar() {
+        assert!(number_parser.parse("e3").is_err());
+        assert!(number_parser.parse("-").is_err());
+        assert!(number_parser.parse("1e").is_err());
+    }
+
+    #[test]
+    fn components() {
+        let res = color_components_parser::<Srgba>.parse("1,0.5,1.");
+        let color = Color::from(Srgba::rgb(1., 0.5, 1.));
+        assert_eq!(res, Ok((color, false)));
+    }
+
+    #[test]
+    fn components2() {
+        let res = color_components_parser::<Srgba>.parse("1, 0.5 , 1. , 0.5");
+        let color = Color::from(Srgba::new(1., 0.5, 1., 0.5));
+        assert_eq!(res, Ok((color, true)));
+    }
+
+    #[test]
+    fn hex1() {
+        assert_eq!(
+            parse_color("#aabbcc", ColorFormat::Hex).unwrap(),
+            (Srgba::rgba_u8(170, 187, 204, 255).into(), true)
+        );
+    }
+
+    #[test]
+    fn hex2() {
+        assert_eq!(
+            parse_color("#aabbcc00", ColorFormat::Hex).unwrap(),
+            (Srgba::rgba_u8(170, 187, 204, 0).into(), true)
+        );
+    }
+
+    #[test]
+    fn hex3() {
+        assert_eq!(
+            parse_color("#aaa", ColorFormat::Hex).unwrap(),
+            (Srgba::rgba_u8(170, 170, 170, 255).into(), true)
+        );
+    }
+
+    #[test]
+    fn fail_hex1() {
+        assert!(parse_color("", ColorFormat::Hex).is_err());
+    }
+
+    #[test]
+    fn fail_hex2() {
+        assert!(parse_color("#a", ColorFormat::Hex).is_err());
+    }
+
+    #[test]
+    fn rgb_percentage() {
+        assert_eq!(
+            parse_color("rgb(100% 0% 50%)", ColorFormat::Rgb).unwrap(),
+            (Srgba::rgb(1., 0., 0.5).into(), true)
+        );
+    }
+
+    #[test]
+    fn calc_percentage() {
+        assert_eq!(
+            parse_color(
+                "rgb(calc(50% + 10%) 0 calc(2 * (10 + 5)))",
+                ColorFormat::Rgb
+            )
+            .unwrap(),
+            (Srgba::rgb(0.6, 0., 30. / 255.).into(), true)
+        );
+    }
+
+    #[test]
+    fn calc_hue() {
+        assert_eq!(
+            parse_color("hsl(calc(90deg + 0.25turn) 50% 50%)", ColorFormat::Hsl).unwrap(),
+            (Hsla::new(180., 0.5, 0.5, 1.).into(), true)
+        );
+    }
+
+    #[test]
+    fn fail_calc_units() {
+        assert!(parse_color("rgb(calc(50% + 10) 0 0)", ColorFormat::Rgb).is_err());
+        assert!(parse_color("rgb(calc(10% * 10%) 0 0)", ColorFormat::Rgb).is_err());
+        assert!(parse_color("rgb(calc(10 / 0) 0 0)", ColorFormat::Rgb).is_err());
+        assert!(parse_color("hsl(calc(90deg) 50% calc(1deg))", ColorFormat::Hsl).is_err());
+    }
+
+    #[test]
+    fn rgb1() {
+        assert_eq!(
+            parse_color("rgb(170 187 204)", ColorFormat::Rgb).unwrap(),
+            (Srgba::rgba_u8(170, 187, 204, 255).into(), true)
+        );
+    }
+
+    #[test]
+    fn rgb2() {
+        assert_eq!(
+            parse_color("rgb(170 187 204 / 0.0)", ColorFormat::Rgb).unwrap(),
+            (Srgba::rgba_u8(170, 187, 204, 0).into(), true)
+        );
+    }
+
+    #[test]
+    fn rgb3() {
+        assert_eq!(
+            parse_color("rgb(   170 187 204/.0%  )", ColorFormat::Rgb).unwrap(),
+            (Srgba::rgba_u8(170, 187, 204, 0).into(), true)
+        );
+    }
+
+    #[test]
+    fn fail_rgb1() {
+        assert!(parse_color("170 187 204", ColorFormat::Rgb).is_err());
+    }
+
+    #[test]
+    fn fail_rgb2() {
+        assert!(parse_color("rgb(1 2)", ColorFormat::Rgb).is_err());
+    }
+    #[test]
+    fn fail_rgb3() {
+        assert!(parse_color("rgb()", ColorFormat::Rgb).is_err());
+    }
+
+    #[test]
+    fn fail_rgb4() {
+        assert!(parse_color("rgb(x 1 1%)", ColorFormat::Rgb).is_err());
+    }
+
+    #[test]
+    fn rgb_legacy() {
+        assert_eq!(
+            parse_color("rgba(255, 255, 255, 0.5)", ColorFormat::RgbLegacy).unwrap(),
+            (Srgba::new(1., 1., 1., 0.5).into(), true)
+        );
+    }
+
+    #[test]
+    fn fail_rgb_legacy_mixed_units() {
+        assert!(parse_color("rgb(1.0%, 1, 1)", ColorFormat::RgbLegacy).is_err());
+    }
+
+    #[test]
+    fn hsl_legacy() {
+        assert_eq!(
+            parse_color("hsla(50, 10%, 10%, 0.5)", ColorFormat::HslLegacy).unwrap(),
+            (Hsla::new(50., 0.1, 0.1, 0.5).into(), true)
+        );
+    }
+
+    #[test]
+    fn oklch1() {
+        assert_eq!(
+            parse_color("oklch(0.5 0.4 0.2)", ColorFormat::Oklch).unwrap(),
+            (Oklcha::new(0.5, 0.4, 0.2, 1.).into(), true)
+        );
+    }
+
+    #[test]
+    fn oklch2() {
+        assert_eq!(
+            parse_color("oklch( 10% 100% 150 / 20% )", ColorFormat::Oklch).unwrap(),
+            (Oklcha::new(0.1, 0.4, 150., 0.2).into(), true)
+        );
+    }
+
+    #[test]
+    fn oklab1() {
+        assert_eq!(
+            parse_color("oklab(0.5 0.1 0.2)", ColorFormat::Oklab).unwrap(),
+            (Oklaba::new(0.5, 0.1, 0.2, 1.).into(), true)
+        );
+    }
+
+    #[test]
+    fn oklab_negative() {
+        assert_eq!(
+            parse_color("oklab(0.5 -0.1 +5e-2)", ColorFormat::Oklab).unwrap(),
+            (Oklaba::new(0.5, -0.1, 0.05, 1.).into(), true)
+        );
+    }
+
+    #[test]
+    fn oklab2() {
+        assert_eq!(
+            parse_color("oklab( 50% 25% none / 20% )", ColorFormat::Oklab).unwrap(),
+            (Oklaba::new(0.5, 0.1, 0., 0.2).into(), true)
+        );
+    }
+
+    #[test]
+    fn hwb1() {
+        assert_eq!(
+            parse_color("hwb(0.5turn 10% 20% / 0.5)", ColorFormat::Hwb).unwrap(),
+            (Hwba::new(180., 0.1, 0.2, 0.5).into(), true)
+        );
+    }
+
+    #[test]
+    fn hwb2() {
+        assert_eq!(
+            parse_color("hwb(none 0% none)", ColorFormat::Hwb).unwrap(),
+            (Hwba::new(0., 0., 0., 1.).into(), true)
+        );
+    }
+
+    #[test]
+    fn hwb_roundtrip() {
+        let color = Srgba::rgb_u8(51, 153, 102);
+        let text = format_color(color.into(), ColorFormat::Hwb, false);
+        assert_eq!(text, "hwb(150 20% 40%)");
+        let (parsed, _) = parse_color(&text, ColorFormat::Hwb).unwrap();
+        assert_eq!(Srgba::from(parsed).to_u8_array(), color.to_u8_array());
+    }
+
+    #[test]
+    fn lab1() {
+        assert_eq!(
+            format_color(Srgba::RED.into(), ColorFormat::Lab, false),
+            "lab(54.29 80.81 69.89)"
+        );
+    }
+
+    #[test]
+    fn lab2() {
+        let (c, _) = parse_color("lab(54.29 80.8 69.89 / 50%)", ColorFormat::Lab).unwrap();
+        let [r, g, b, a] = Srgba::from(c).to_u8_array();
+        assert_eq!((r, g, b, a), (255, 0, 0, 128));
+    }
+
+    #[test]
+    fn lch1() {
+        assert_eq!(
+            format_color(Srgba::RED.into(), ColorFormat::Lch, false),
+            "lch(54.29 106.84 40.9)"
+        );
+    }
+
+    #[test]
+    fn lch2() {
+        let (c, _) = parse_color("lch(54.29% 71.23% 40.9deg)", ColorFormat::Lch).unwrap();
+        let [r, g, b] = Srgba::from(c).to_u8_array_no_alpha();
+        assert_eq!((r, g, b), (255, 0, 0));
+    }
+
+    #[test]
+    fn css_color_srgb() {
+        assert_eq!(
+            parse_color("color(srgb 1 50% none / 0.5)", ColorFormat::ColorSrgb).unwrap(),
+            (LinearRgba::from(Srgba::new(1., 0.5, 0., 0.5)).into(), true)
+        );
+    }
+
+    #[test]
+    fn css_color_display_p3() {
+        let (c, _) = parse_color("color(display-p3 1 0 0)", ColorFormat::ColorDisplayP3).unwrap();
+        let c = LinearRgba::from(c);
+        assert!(c.red > 1. && c.green < 0. && c.blue < 0.);
+        assert_eq!(
+            format_color(Srgba::RED.into(), ColorFormat::ColorDisplayP3, false),
+            "color(display-p3 0.9176 0.2003 0.1386)"
+        );
+    }
+
+    #[test]
+    fn css_color_negative_channel() {
+        let (c, _) = parse_color(
+            "color(display-p3 -0.1 0.5 0.5)",
+            ColorFormat::ColorDisplayP3,
+        )
+        .unwrap();
+        let values = CssColorSpace::DisplayP3.to_values(c.into());
+        assert!((values[0] + 0.1).abs() < 1e-4);
+        assert_eq!(
+            restyle(
+                "color(display-p3 -0.1 0.5 0.5)",
+                ColorFormat::ColorDisplayP3
+            ),
+            "color(display-p3 -0.1 0.5 0.5)"
+        );
+    }
+
+    #[test]
+    fn css_color_roundtrip() {
+        let color = Srgba::rgba_u8(51, 153, 102, 128);
+        for format in ColorFormat::iter().filter(|f| f.css_color_space().is_some()) {
+            let text = format_color(color.into(), format, false);
+            let (parsed, _) = parse_color(&text, format).unwrap();
+            assert_eq!(Srgba::from(parsed).to_u8_array(), color.to_u8_array());
+        }
+    }
+
+    #[test]
+    fn css_color_xyz_alias() {
+        assert_eq!(
+            parse_color_unknown_format("color(xyz 0.2 0.3 0.4)").map(|(_, f, _)| f),
+            Ok(ColorFormat::ColorXyzD65)
+        );
+    }
+
+    #[test]
+    fn fail_css_color_wrong_space() {
+        assert!(parse_color("color(rec2020 1 1 1)", ColorFormat::ColorDisplayP3).is_err());
+    }
+
+    #[test]
+    fn named1() {
+        assert_eq!(
+            parse_color("RebeccaPurple", ColorFormat::Named).unwrap(),
+            (Srgba::rgb_u8(0x66, 0x33, 0x99).into(), true)
+        );
+        assert_eq!(
+            parse_color_unknown_format("transparent"),
+            Ok((Srgba::NONE.into(), ColorFormat::Named, true))
+        );
+    }
+
+    #[test]
+    fn named_nearest() {
+        assert_eq!(
+            nearest_named_color(Srgba::rgb_u8(255, 99, 71).into()),
+            ("tomato", 0.)
+        );
+        let (name, distance) = nearest_named_color(Srgba::rgb_u8(250, 100, 70).into());
+        assert_eq!(name, "tomato");
+        assert!(distance > 0.);
+        assert_eq!(
+            format_color(Srgba::rgb_u8(0, 255, 255).into(), ColorFormat::Named, false),
+            "aqua"
+        );
+    }
+
+    #[test]
+    fn named_nearest_distance() {
+        let tomato = Srgba::rgb_u8(255, 99, 71).into();
+        assert_eq!(nearest_match_distance(tomato, ColorFormat::Named), None);
+        let near_tomato = Srgba::rgb_u8(250, 100, 70).into();
+        assert!(nearest_match_distance(near_tomato, ColorFormat::Named).is_some_and(|d| d > 0.));
+        assert_eq!(nearest_match_distance(near_tomato, ColorFormat::Hex), None);
+    }
+
+    #[test]
+    fn fail_named() {
+        assert!(parse_color("notacolor", ColorFormat::Named).is_err());
+    }
+
+    fn assert_same_u8(a: Color, b: Color) {
+        assert_eq!(Srgba::from(a).to_u8_array(), Srgba::from(b).to_u8_array());
+    }
+
+    #[test]
+    fn relative_oklch() {
+        let (c, _) = parse_color("oklch(from #3a7 calc(l + 0.1) c h)", ColorFormat::Oklch).unwrap();
+        let mut expected = Oklcha::from(Srgba::hex("#3a7").unwrap());
+        expected.lightness += 0.1;
+        assert_eq!(c, expected.into());
+    }
+
+    #[test]
+    fn relative_rgb_alpha() {
+        assert_eq!(
+            parse_color("rgb(from #fff r g b / 50%)", ColorFormat::Rgb).unwrap(),
+            (Srgba::new(1., 1., 1., 0.5).into(), true)
+        );
+    }
+
+    #[test]
+    fn relative_hsl_calc() {
+        let (c, _) = parse_color(
+            "hsl(from hsl(0 50% 40%) h s calc(l + 20))",
+            ColorFormat::Hsl,
+        )
+        .unwrap();
+        assert_same_u8(c, Hsla::new(0., 0.5, 0.6, 1.).into());
+    }
+
+    #[test]
+    fn relative_hwb_calc() {
+        let (c, _) = parse_color(
+            "hwb(from hwb(120 10% 20%) h calc(w + 10) b)",
+            ColorFormat::Hwb,
+        )
+        .unwrap();
+        assert_same_u8(c, Hwba::new(120., 0.2, 0.2, 1.).into());
+    }
+
+    #[test]
+    fn relative_keeps_origin_alpha() {
+        let (c, _) = parse_color("hsl(from #33669980 h s l)", ColorFormat::Hsl).unwrap();
+        assert_same_u8(c, Srgba::hex("#33669980").unwrap().into());
+    }
+
+    #[test]
+    fn relative_nested_calc() {
+        let (c, _) = parse_color(
+            "rgb(from rgb(from tomato r g b) calc((r - 55) / 2) calc(g*2) 0 / calc(alpha / 4))",
+            ColorFormat::Rgb,
+        )
+        .unwrap();
+        assert_same_u8(c, Srgba::rgba_u8(100, 198, 0, 64).into());
+    }
+
+    #[test]
+    fn relative_css_color() {
+        let (c, _) = parse_color(
+            "color(from red display-p3 r g b)",
+            ColorFormat::ColorDisplayP3,
+        )
+        .unwrap();
+        assert_same_u8(c, Srgba::RED.into());
+    }
+
+    #[test]
+    fn relative_lch_hue() {
+        let (c, _) = parse_color(
+            "lch(from lch(50 40 350) l c calc(h + 20))",
+            ColorFormat::Lch,
+        )
+        .unwrap();
+        assert_same_u8(c, LinearRgba::from(CieLcha::new(50., 40., 10., 1.)).into());
+    }
+
+    #[test]
+    fn fail_relative() {
+        assert!(parse_color("rgb(from #fff x g b)", ColorFormat::Rgb).is_err());
+        assert!(parse_color("rgb(from nope r g b)", ColorFormat::Rgb).is_err());
+        assert!(parse_color("rgb(r g b)", ColorFormat::Rgb).is_err());
+    }
+
+    #[test]
+    fn color_mix_srgb() {
+        let (c, format, _) =
+            parse_color_unknown_format("color-mix(in srgb, #ff0000, #0000ff)").unwrap();
+        let c = Srgba::from(c).to_f32_array();
+        assert!(
+            c.iter()
+                .zip([0.5, 0., 0.5, 1.])
+                .all(|(a, b)| (a - b).abs() < 1e-4)
+        );
+        assert_eq!(format, ColorFormat::ColorSrgb);
+    }
+
+    #[test]
+    fn color_mix_percentages() {
+        let (c, _) = parse_color(
+            "color-mix(in srgb-linear, 25% white, black 25%)",
+            ColorFormat::ColorSrgbLinear,
+        )
+        .unwrap();
+        assert_same_u8(c, LinearRgba::new(0.5, 0.5, 0.5, 0.5).into());
+        let (c, _) =
+            parse_color("color-mix(in oklab, white 30%, black)", ColorFormat::Oklab).unwrap();
+        assert_same_u8(c, Oklaba::new(0.3, 0., 0., 1.).into());
+    }
+
+    #[test]
+    fn color_mix_calc() {
+        let (c, _) = parse_color(
+            "color-mix(in oklab, white calc(10% * 3), black)",
+            ColorFormat::Oklab,
+        )
+        .unwrap();
+        assert_same_u8(c, Oklaba::new(0.3, 0., 0., 1.).into());
+    }
+
+    #[test]
+    fn color_mix_hue() {
+        let mix = |s: &str| Oklcha::from(parse_color(s, ColorFormat::Oklch).unwrap().0).hue;
+        let a = "oklch(0.7 0.1 350)";
+        let b = "oklch(0.7 0.1 50)";
+        assert!((mix(&format!("color-mix(in oklch, {a}, {b})")) - 20.).abs() < 0.1);
+        assert!((mix(&format!("color-mix(in oklch longer hue, {a}, {b})")) - 200.).abs() < 0.1);
+        assert!((mix(&format!("color-mix(in oklch increasing hue, {a}, {b})")) - 20.).abs() < 0.1);
+        assert!((mix(&format!("color-mix(in oklch decreasing hue, {a}, {b})")) - 200.).abs() < 0.1);
+    }
+
+    #[test]
+    fn color_mix_achromatic_hue() {
+        let (c, _) =
+            parse_color("color-mix(in lch, white, lch(50 60 120))", ColorFormat::Lch).unwrap();
+        let hue = CieLcha::from(LinearRgba::from(c)).hue;
+        assert!((hue - 120.).abs() < 0.5);
+    }
+
+    #[test]
+    fn color_mix_nested() {
+        let (c, format, _) = parse_color_unknown_format(
+            "color-mix(in hsl, color-mix(in srgb, red, red) 50%, hsl(120 100% 50%) 50%)",
+        )
+        .unwrap();
+        assert_same_u8(c, Srgba::rgb_u8(255, 255, 0).into());
+        assert_eq!(format, ColorFormat::Hsl);
+    }
+
+    #[test]
+    fn fail_color_mix_deeply_nested() {
+        let mut s = "nope".to_string();
+        for _ in 0..5 {
+            s = format!("color-mix(in srgb, red, {s})");
+        }
+        let start = std::time::Instant::now();
+        assert!(parse_color_unknown_format(&s).is_err());
+        assert!(start.elapsed() < std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn fail_color_mix() {
+        assert!(parse_color_unknown_format("color-mix(in srgb, red)").is_err());
+        assert!(parse_color_unknown_format("color-mix(in nope, red, blue)").is_err());
+        assert!(parse_color_unknown_format("color-mix(in srgb, red 0%, blue 0%)").is_err());
+        assert!(parse_color_unknown_format("color-mix(in srgb, red 150%, blue)").is_err());
+        assert!(parse_color("color-mix(in srgb, red, blue)", ColorFormat::Hex).is_err());
+    }
+
+    #[test]
+    fn raw_rgb_float1() {
+        assert_eq!(
+            parse_color("0,.1,1.,0.2", ColorFormat::RawRgbFloat).unwrap(),
+            (Srgba::new(0.0, 0.1, 1.0, 0.2).into(), true)
+        );
+    }
+
+    #[test]
+    fn raw_rgb_float2() {
+        assert_eq!(
+            parse_color("0,.1,1.", ColorFormat::RawRgbFloat).unwrap(),
+            (Srgba::new(0.0, 0.1, 1.0, 1.0).into(), false)
+        );
+    }
+
+    #[test]
+    fn raw_rgb_float3() {
+        assert_eq!(
+            parse_color("0.0,     0.5,   0.8", ColorFormat::RawRgbFloat).unwrap(),
+            (Srgba::new(0.0, 0.5, 0.8, 1.0).into(), false)
+        );
+    }
+
+    #[test]
+    fn fail_raw_rgb_float1() {
+        assert!(parse_color("0.0 0.5, 0.8", ColorFormat::RawRgbFloat).is_err());
+    }
+
+    #[test]
+    fn fail_raw_rgb_float2() {
+        assert!(parse_color("0", ColorFormat::RawRgbFloat).is_err());
+    }
+
+    #[test]
+    fn fail_raw_rgb_float3() {
+        assert!(parse_color("0, 0", ColorFormat::RawRgbFloat).is_err());
+    }
+
+    #[test]
+    fn fail_raw_rgb_float4() {
+        assert!(parse_color("0, 0, 0, 0, 0", ColorFormat::RawRgbFloat).is_err());
+    }
+
+    #[test]
+    fn raw_rgb_linear_scientific() {
+        assert_eq!(
+            parse_color("-0.5, 1e-3, 2.5", ColorFormat::RawRgbLinear),
+            Ok((LinearRgba::rgb(-0.5, 0.001, 2.5).into(), false))
+        );
+    }
+
+    #[test]
+    fn raw_hex_literal() {
+        assert_eq!(
+            parse_color("0x001122", ColorFormat::HexLiteral),
+            Ok((Srgba::rgb_u8(0, 17, 34).into(), false))
+        );
+    }
+
+    #[test]
+    fn raw_hex_literal_alpha() {
+        assert_eq!(
+            parse_color("0x33001122", ColorFormat::HexLiteral),
+            Ok((Srgba::rgba_u8(0, 17, 34, 51).into(), true))
+        );
+    }
+
+    #[test]
+    fn parse_error_span() {
+        let e = parse_color("hsl(10, 50, 50%)", ColorFormat::HslLegacy).unwrap_err();
+        assert_eq!(e.span, 10..11);
+        assert_eq!(e.format, Some(ColorFormat::HslLegacy));
+        assert_eq!(e.expected, "expected `%` for hsl saturation");
+
+        let e = parse_color("  rgb(1 2 x)", ColorFormat::Rgb).unwrap_err();
+        assert_eq!(e.span, 10..11);
+        assert!(e.expected.ends_with("for rgb blue"));
+    }
+
+    #[test]
+    fn parse_error_hex() {
+        let e = parse_color("#abcde", ColorFormat::Hex).unwrap_err();
+        assert_eq!(e.span, 1..6);
+        assert_eq!(e.expected, "hex must have 3, 4, 6 or 8 digits");
+
+        let e = parse_color("#abxde", ColorFormat::Hex).unwrap_err();
+        assert_eq!(e.span, 3..4);
+        assert_eq!(e.expected, "expected a hex digit");
+    }
+
+    #[test]
+    fn parse_error_unknown_format() {
+        let e = parse_color_unknown_format("qwerty").unwrap_err();
+        assert_eq!(e.span, 0..6);
+        assert_eq!(e.format, None);
+
+        // The format that got the furthest is reported
+        let e = parse_color_unknown_format("oklch(0.5 x 20)").unwrap_err();
+        assert_eq!(e.span, 10..11);
+        assert_eq!(e.format, Some(ColorFormat::Oklch));
+        assert_eq!(
+            e.to_string(),
+            "invalid oklch: expected number, percentage or `none` for oklch chroma"
+        );
+    }
+
+    fn detected(s: &str, hints: &[ColorFormat]) -> (Vec<ColorFormat>, bool) {
+        let detection = detect_formats(s, hints).unwrap();
+        let formats = detection.likely().map(|m| m.format).collect();
+        (formats, detection.ambiguous)
+    }
+
+    #[test]
+    fn detect_raw_ranges() {
+        use ColorFormat as F;
+        assert_eq!(detected("255, 128, 0", &[]), (vec![F::RawRgb], false));
+        assert_eq!(detected("0.7, 0.15, 120", &[]), (vec![F::RawOklch], false));
+        assert_eq!(
+            detected("1.5, -0.2, 0.3", &[]),
+            (vec![F::RawRgbLinear], false)
+        );
+        assert_eq!(
+            detected("0.2, 0.5, 0.9", &[]),
+            (vec![F::RawRgbFloat, F::RawRgbLinear], true)
+        );
+        assert_eq!(detected("#abc", &[]), (vec![F::Hex], false));
+    }
+
+    #[test]
+    fn detect_hints() {
+        use ColorFormat as F;
+        let detection = detect_formats("0.2, 0.5, 0.9", &[F::RawOklch, F::RawRgbLinear]).unwrap();
+        assert_eq!(detection.best().format, F::RawRgbLinear);
+        assert!(!detection.ambiguous);
+
+        // Hints don't override a clearly better match
+        let detection = detect_formats("0.7, 0.15, 120", &[F::RawRgbLinear]).unwrap();
+        assert_eq!(detection.best().format, F::RawOklch);
+
+        assert_eq!(
+            parse_color_unknown_format("0.2, 0.5, 0.9").map(|(_, f, _)| f),
+            Ok(F::RawRgbFloat)
+        );
+    }
+
+    fn found(text: &str, options: FindOptions) -> Vec<(&str, ColorFormat)> {
+        find_colors_with(text, options)
+            .into_iter()
+            .map(|(range, _, format, _)| (&text[range], format))
+            .collect()
+    }
+
+    #[test]
+    fn find_colors_boundaries() {
+        use ColorFormat as F;
+        let text = "a { color: #abc; background: rgb(1 2 3 / 50%); border: #abcdefg-id }";
+        assert_eq!(
+            found(text, FindOptions::default()),
+            vec![("#abc", F::Hex), ("rgb(1 2 3 / 50%)", F::Rgb)]
+        );
+        assert_eq!(
+            found(
+                "é 0xff00ff, x#fff, foo(#fff), #fff.",
+                FindOptions::default()
+            ),
+            vec![
+                ("0xff00ff", F::HexLiteral),
+                ("#fff", F::Hex),
+                ("#fff", F::Hex)
+            ]
+        );
+        assert_eq!(find_colors("é #fff")[0].0, 3..7, "ranges are in bytes");
+    }
+
+    #[test]
+    fn find_colors_nested() {
+        use ColorFormat as F;
+        assert_eq!(
+            found(
+                "color-mix(in oklch, red, #00f) oklch(from #fff l c h)",
+                FindOptions::default()
+            ),
+            vec![
+                ("color-mix(in oklch, red, #00f)", F::Oklch),
+                ("oklch(from #fff l c h)", F::Oklch)
+            ]
+        );
+    }
+
+    #[test]
+    fn find_colors_options() {
+        use ColorFormat as F;
+        let text = "tomato foo(0.5, 0.1, 1.0) 12, 34, 56, 78, 90";
+        assert!(find_colors(text).is_empty());
+        assert_eq!(
+            found(
+                text,
+                FindOptions {
+                    raw: true,
+                    named: true
+                }
+            ),
+            vec![("tomato", F::Named), ("0.5, 0.1, 1.0", F::RawRgbFloat)]
+        );
+
+        // Short hex constants are only colors with an explicit format
+        let text = "tft.fill(0xF81F); flags = 0x1234; mask = 0xff00ff;";
+        assert_eq!(
+            found(
+                text,
+                FindOptions {
+                    raw: true,
+                    named: true
+                }
+            ),
+            vec![("0xff00ff", F::HexLiteral)]
+        );
+
+        let text = r"printf '\e[38;2;255;0;0mred\x1b[0m' rgb:ffff/0000/0000 rgbi:1/0/0";
+        assert_eq!(
+            found(text, FindOptions::default()),
+            vec![
+                (r"\e[38;2;255;0;0m", F::AnsiTruecolor),
+                ("rgb:ffff/0000/0000", F::X11Rgb),
+                ("rgbi:1/0/0", F::X11Rgbi)
+            ]
+        );
+        let text = r"set -g status-style bg=colour196,fg=\033[38;5;15m; color1 = 2";
+        assert_eq!(
+            found(text, FindOptions::default()),
+            vec![("colour196", F::Ansi256), (r"\033[38;5;15m", F::Ansi256)]
+        );
+    }
+
+    #[test]
+    fn template_confidence() {
+        let template = ColorFormat::Template(0);
+        for (s, format) in [
+            ("255, 128, 0", ColorFormat::RawRgb),
+            ("0.5, 0.25, 1.0", ColorFormat::RawRgbFloat),
+            ("#ff8000", ColorFormat::Hex),
+        ] {
+            assert!(template.confidence(s) < format.confidence(s));
+        }
+    }
+
+    #[test]
+    fn glsl1() {
+        assert_eq!(
+            parse_color("vec3(0.5, 0.25, 1.0)", ColorFormat::GlslLinear),
+            Ok((LinearRgba::rgb(0.5, 0.25, 1.).into(), false))
+        );
+    }
+
+    #[test]
+    fn glsl2() {
+        assert_eq!(
+            restyle("vec3(0.5,0.25,1.0)", ColorFormat::GlslLinear),
+            "vec3(0.5,0.25,1.0)"
+        );
+    }
+
+    #[test]
+    fn fail_glsl1() {
+        assert!(parse_color("vec3(0.5, 0.25)", ColorFormat::Glsl).is_err());
+    }
+
+    #[test]
+    fn fail_glsl2() {
+        assert!(parse_color("float3(0.5, 0.25, 1.0)", ColorFormat::Glsl).is_err());
+    }
+
+    #[test]
+    fn wgsl1() {
+        assert_eq!(
+            parse_color("vec4<f32>(0.5f, 0.25, 1, 0.5)", ColorFormat::Wgsl),
+            Ok((Srgba::new(0.5, 0.25, 1., 0.5).into(), true))
+        );
+    }
+
+    #[test]
+    fn hlsl1() {
+        assert_eq!(
+            parse_color("float3(0.5)", ColorFormat::HlslLinear),
+            Ok((LinearRgba::rgb(0.5, 0.5, 0.5).into(), false))
+        );
+    }
+
+    #[test]
+    fn shader_output() {
+        let color = LinearRgba::new(0.5, 0.25, 1., 0.5);
+        assert_eq!(
+            format_color(color, ColorFormat::WgslLinear, true),
+            "vec4f(0.5, 0.25, 1.0, 0.5)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::HlslLinear, false),
+            "float3(0.5, 0.25, 1.0)"
+        );
+    }
+
+    #[test]
+    fn detect_shader_vectors() {
+        use ColorFormat as F;
+        assert_eq!(
+            detected("vec3(0.5, 0.25, 1.0)", &[]),
+            (vec![F::GlslLinear, F::Glsl], true)
+        );
+        assert_eq!(
+            detected("float4(0.5, 0.25, 1.0, 1.0)", &[F::Hlsl]).0[0],
+            F::Hlsl
+        );
+    }
+
+    #[test]
+    fn bevy_srgb1() {
+        assert_eq!(
+            parse_color("Color::srgba(1.0, 0.5, 0.0, 0.5)", ColorFormat::BevySrgb),
+            Ok((Srgba::new(1., 0.5, 0., 0.5).into(), true))
+        );
+    }
+
+    #[test]
+    fn fail_bevy_srgb1() {
+        assert!(parse_color("Color::srgb(0.5)", ColorFormat::BevySrgb).is_err());
+    }
+
+    #[test]
+    fn bevy_linear_rgb1() {
+        assert_eq!(
+            parse_color(
+                "Color::linear_rgb(0.5, 0.25, 1.0)",
+                ColorFormat::BevyLinearRgb
+            ),
+            Ok((LinearRgba::rgb(0.5, 0.25, 1.).into(), false))
+        );
+    }
+
+    #[test]
+    fn bevy_oklch1() {
+        assert_eq!(
+            parse_color("Color::oklch(0.5, 0.1, 90.0)", ColorFormat::BevyOklch),
+            Ok((Oklcha::lch(0.5, 0.1, 90.).into(), false))
+        );
+    }
+
+    #[test]
+    fn bevy_hex1() {
+        assert_eq!(
+            parse_color("Srgba::hex(\"f00\")", ColorFormat::BevyHex),
+            Ok((Srgba::rgb(1., 0., 0.).into(), true))
+        );
+        assert_eq!(
+            restyle("Srgba::hex(\"#ABC\")", ColorFormat::BevyHex),
+            "Srgba::hex(\"#ABC\")"
+        );
+    }
+
+    #[test]
+    fn fail_bevy_hex1() {
+        assert!(parse_color("Srgba::hex(\"ff000\")", ColorFormat::BevyHex).is_err());
+    }
+
+    #[test]
+    fn unity_color1() {
+        assert_eq!(
+            parse_color("new  Color(1f, 0.5f, 0f, 1f)", ColorFormat::UnityColor),
+            Ok((Srgba::new(1., 0.5, 0., 1.).into(), true))
+        );
+    }
+
+    #[test]
+    fn unity_color32_1() {
+        assert_eq!(
+            parse_color("new Color32(255, 0, 51, 255)", ColorFormat::UnityColor32),
+            Ok((Srgba::new(1., 0., 0.2, 1.).into(), true))
+        );
+    }
+
+    #[test]
+    fn fail_unity_color32_alpha() {
+        assert!(parse_color("new Color32(255, 0, 51)", ColorFormat::UnityColor32).is_err());
+    }
+
+    #[test]
+    fn godot_color8_1() {
+        assert_eq!(
+            parse_color("Color8(255, 0, 51)", ColorFormat::GodotColor8),
+            Ok((Srgba::rgb(1., 0., 0.2).into(), false))
+        );
+    }
+
+    #[test]
+    fn fail_godot_color8_range() {
+        assert!(parse_color("Color8(256, 0, 0)", ColorFormat::GodotColor8).is_err());
+    }
+
+    #[test]
+    fn godot_hex1() {
+        assert_eq!(
+            parse_color("Color(\"#ff000080\")", ColorFormat::GodotHex),
+            Ok((Srgba::new(1., 0., 0., 128. / 255.).into(), true))
+        );
+    }
+
+    #[test]
+    fn bevy_output() {
+        let color = Srgba::new(1., 0.5, 0., 0.5).into();
+        assert_eq!(
+            format_color(color, ColorFormat::BevySrgb, true),
+            "Color::srgba(1.0, 0.5, 0.0, 0.5)"
+        );
+    }
+
+    #[test]
+    fn unity_output() {
+        let color = Srgba::new(1., 0.5, 0., 0.5).into();
+        assert_eq!(
+            format_color(color, ColorFormat::UnityColor, false),
+            "new Color(1.0f, 0.5f, 0.0f)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::UnityColor32, false),
+            "new Color32(255, 128, 0, 128)"
+        );
+    }
+
+    #[test]
+    fn godot_output() {
+        let color = Srgba::new(1., 0.5, 0., 0.5).into();
+        assert_eq!(
+            format_color(color, ColorFormat::GodotColor8, false),
+            "Color8(255, 128, 0)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::GodotHex, true),
+            "Color(\"#ff800080\")"
+        );
+    }
+
+    #[test]
+    fn detect_engine_constructors() {
+        use ColorFormat as F;
+        assert_eq!(
+            detected("Color(0.5, 0.25, 1.0)", &[]),
+            (vec![F::GodotColor], false)
+        );
+        assert_eq!(
+            detected("new Color(0.5f, 0.25f, 1f)", &[]),
+            (vec![F::UnityColor], false)
+        );
+        assert_eq!(detected("Color(\"#abc\")", &[]), (vec![F::GodotHex], false));
+        let found = find_colors("let c = Color::srgb(1.0, 0.0, 0.0); new Color32(0, 0, 0, 255)");
+        let formats: Vec<_> = found.iter().map(|f| f.2).collect();
+        assert_eq!(formats, [F::BevySrgb, F::UnityColor32]);
+    }
+
+    #[test]
+    fn swiftui1() {
+        assert_eq!(
+            parse_color(
+                "Color(red: 1, green: 0.5, blue: 0.0)",
+                ColorFormat::SwiftuiColor
+            ),
+            Ok((Srgba::rgb(1., 0.5, 0.).into(), false))
+        );
+    }
+
+    #[test]
+    fn fail_swiftui_order() {
+        assert!(
+            parse_color(
+                "Color(green: 1, red: 0.5, blue: 0)",
+                ColorFormat::SwiftuiColor
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn uikit1() {
+        assert_eq!(
+            parse_color(
+                "UIColor(red: 1, green: 0.5, blue: 0, alpha: 0.5)",
+                ColorFormat::UikitColor
+            ),
+            Ok((Srgba::new(1., 0.5, 0., 0.5).into(), true))
+        );
+    }
+
+    #[test]
+    fn fail_uikit_alpha() {
+        assert!(
+            parse_color(
+                "UIColor(red: 1, green: 0.5, blue: 0)",
+                ColorFormat::UikitColor
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn compose1() {
+        assert_eq!(
+            parse_color(
+                "Color(red = 1f, green = 0.5f, blue = 0f, alpha = 0.5f)",
+                ColorFormat::ComposeColor
+            ),
+            Ok((Srgba::new(1., 0.5, 0., 0.5).into(), true))
+        );
+    }
+
+    #[test]
+    fn color_argb_hex1() {
+        assert_eq!(
+            parse_color("Color(0x80FF0000)", ColorFormat::ColorArgbHex),
+            Ok((Srgba::new(1., 0., 0., 128. / 255.).into(), true))
+        );
+        assert_eq!(
+            restyle("Color(0xffabcdef)", ColorFormat::ColorArgbHex),
+            "Color(0xffabcdef)"
+        );
+    }
+
+    #[test]
+    fn fail_color_argb_hex_alpha() {
+        assert!(parse_color("Color(0xFF0000)", ColorFormat::ColorArgbHex).is_err());
+    }
+
+    #[test]
+    fn flutter_argb1() {
+        assert_eq!(
+            parse_color("Color.fromARGB(255, 255, 0, 51)", ColorFormat::FlutterArgb),
+            Ok((Srgba::rgb(1., 0., 0.2).into(), true))
+        );
+    }
+
+    #[test]
+    fn flutter_rgbo1() {
+        assert_eq!(
+            parse_color("Color.fromRGBO(255, 0, 51, 0.5)", ColorFormat::FlutterRgbo),
+            Ok((Srgba::new(1., 0., 0.2, 0.5).into(), true))
+        );
+    }
+
+    #[test]
+    fn fail_flutter_rgbo_bytes() {
+        assert!(parse_color("Color.fromRGBO(255, 0, 0.5, 1)", ColorFormat::FlutterRgbo).is_err());
+    }
+
+    #[test]
+    fn swift_output() {
+        assert_eq!(
+            format_color(
+                Srgba::new(1., 0.5, 0., 0.5).into(),
+                ColorFormat::SwiftuiColor,
+                true
+            ),
+            "Color(red: 1.0, green: 0.5, blue: 0.0, opacity: 0.5)"
+        );
+        assert_eq!(
+            format_color(
+                Srgba::rgb(1., 0.5, 0.).into(),
+                ColorFormat::UikitColor,
+                false
+            ),
+            "UIColor(red: 1.0, green: 0.5, blue: 0.0, alpha: 1.0)"
+        );
+    }
+
+    #[test]
+    fn android_output() {
+        let color = Srgba::new(1., 0.5, 0., 0.5).into();
+        assert_eq!(
+            format_color(color, ColorFormat::ComposeColor, false),
+            "Color(red = 1.0f, green = 0.5f, blue = 0.0f)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::ColorArgbHex, false),
+            "Color(0x80FF8000)"
+        );
+    }
+
+    #[test]
+    fn flutter_output() {
+        let color = Srgba::new(1., 0.5, 0., 0.5).into();
+        assert_eq!(
+            format_color(color, ColorFormat::FlutterArgb, false),
+            "Color.fromARGB(128, 255, 128, 0)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::FlutterRgbo, false),
+            "Color.fromRGBO(255, 128, 0, 0.5)"
+        );
+    }
+
+    #[test]
+    fn detect_mobile_constructors() {
+        use ColorFormat as F;
+        assert_eq!(
+            detected("Color(red: 1, green: 0.5, blue: 0)", &[]),
+            (vec![F::SwiftuiColor], false)
+        );
+        assert_eq!(
+            detected("Color(0xFFABCDEF)", &[]),
+            (vec![F::ColorArgbHex], false)
+        );
+        let found = find_colors(
+            "Color.fromRGBO(1, 2, 3, 1.0), UIColor(red: 1, green: 0, blue: 0, alpha: 1)",
+        );
+        let formats: Vec<_> = found.iter().map(|f| f.2).collect();
+        assert_eq!(formats, [F::FlutterRgbo, F::UikitColor]);
+    }
+
+    #[test]
+    fn x11_rgb1() {
+        assert_eq!(
+            parse_color("rgb:ffff/8080/0", ColorFormat::X11Rgb),
+            Ok((Srgba::rgb(1., 0x80 as f32 / 255., 0.).into(), false))
+        );
+        assert_eq!(restyle("rgb:FF/80/00", ColorFormat::X11Rgb), "rgb:FF/80/00");
+    }
+
+    #[test]
+    fn fail_x11_rgb_digits() {
+        assert!(parse_color("rgb:fffff/0/0", ColorFormat::X11Rgb).is_err());
+    }
+
+    #[test]
+    fn x11_rgbi1() {
+        assert_eq!(
+            parse_color("rgbi:1/0.5/0", ColorFormat::X11Rgbi),
+            Ok((Srgba::rgb(1., 0.5, 0.).into(), false))
+        );
+    }
+
+    #[test]
+    fn fail_x11_rgbi_range() {
+        assert!(parse_color("rgbi:2/0/0", ColorFormat::X11Rgbi).is_err());
+    }
+
+    #[test]
+    fn ansi_truecolor1() {
+        assert_eq!(
+            parse_color(r"\e[38;2;255;128;0m", ColorFormat::AnsiTruecolor),
+            Ok((Srgba::rgb_u8(255, 128, 0).into(), false))
+        );
+        assert_eq!(
+            parse_color("\x1b[48;2;255;128;0m", ColorFormat::AnsiTruecolor),
+            Ok((Srgba::rgb_u8(255, 128, 0).into(), false))
+        );
+        assert_eq!(
+            restyle(r"\033[48;2;1;2;3m", ColorFormat::AnsiTruecolor),
+            r"\033[48;2;1;2;3m"
+        );
+    }
+
+    #[test]
+    fn fail_ansi_truecolor_end() {
+        assert!(parse_color(r"\e[38;2;255;128;0", ColorFormat::AnsiTruecolor).is_err());
+    }
+
+    #[test]
+    fn ansi256_1() {
+        assert_eq!(
+            parse_color("colour196", ColorFormat::Ansi256),
+            Ok((Srgba::rgb_u8(255, 0, 0).into(), false))
+        );
+        assert_eq!(restyle("colour67", ColorFormat::Ansi256), "colour67");
+    }
+
+    #[test]
+    fn ansi256_2() {
+        assert_eq!(
+            parse_color(r"\x1b[38;5;244m", ColorFormat::Ansi256),
+            Ok((Srgba::rgb_u8(128, 128, 128).into(), false))
+        );
+        assert_eq!(
+            restyle(r"\e[38;5;67m", ColorFormat::Ansi256),
+            r"\e[38;5;67m"
+        );
+    }
+
+    #[test]
+    fn fail_ansi256_range() {
+        assert!(parse_color("256", ColorFormat::Ansi256).is_err());
+    }
+
+    #[test]
+    fn ansi256_detect() {
+        // Plain integers are too common to be guessed as colors
+        assert!(parse_color_unknown_format("208").is_err());
+        assert_eq!(
+            detected("colour208", &[]),
+            (vec![ColorFormat::Ansi256], false)
+        );
+        assert_eq!(
+            detected(r"\e[38;5;208m", &[]),
+            (vec![ColorFormat::Ansi256], false)
+        );
+    }
+
+    #[test]
+    fn terminal_output() {
+        let color = Srgba::rgb_u8(255, 128, 0).into();
+        assert_eq!(
+            format_color(color, ColorFormat::X11Rgb, false),
+            "rgb:ffff/8080/0000"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::X11Rgbi, false),
+            "rgbi:1.0/0.502/0.0"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::AnsiTruecolor, false),
+            r"\e[38;2;255;128;0m"
+        );
+    }
+
+    #[test]
+    fn ansi256_output() {
+        let color = Srgba::rgb_u8(255, 128, 0).into();
+        assert_eq!(format_color(color, ColorFormat::Ansi256, false), "208");
+        assert!(nearest_match_distance(color, ColorFormat::Ansi256).is_some_and(|d| d > 0.));
+    }
+
+    #[test]
+    fn cmyk1() {
+        assert_eq!(
+            to_hex("cmyk(0%, 81%, 81%, 30%)", ColorFormat::Cmyk),
+            ("#b32222".into(), false)
+        );
+        assert_eq!(
+            to_hex("cmyk(0, 81, 81, 30)", ColorFormat::Cmyk),
+            ("#b32222".into(), false)
+        );
+        assert_eq!(
+            restyle("cmyk(0, 75, 50, 20)", ColorFormat::Cmyk),
+            "cmyk(0, 75, 50, 20)"
+        );
+    }
+
+    #[test]
+    fn fail_cmyk_channels() {
+        assert!(parse_color("cmyk(0%, 81%, 81%)", ColorFormat::Cmyk).is_err());
+    }
+
+    #[test]
+    fn device_cmyk1() {
+        assert_eq!(
+            to_hex("device-cmyk(0 0.81 81% 0.3 / 50%)", ColorFormat::DeviceCmyk),
+            ("#b3222280".into(), true)
+        );
+        assert_eq!(
+            to_hex("device-cmyk(0, 0.81, 0.81, 0.3)", ColorFormat::DeviceCmyk),
+            ("#b32222".into(), true)
+        );
+        assert_eq!(
+            restyle("device-cmyk(0 0.75 0.5 0.2 / 0.5)", ColorFormat::DeviceCmyk),
+            "device-cmyk(0 0.75 0.5 0.2 / 0.5)"
+        );
+    }
+
+    #[test]
+    fn raw_cmyk1() {
+        assert_eq!(
+            to_hex("0, 81, 81, 30", ColorFormat::RawCmyk),
+            ("#b32222".into(), false)
+        );
+        assert_eq!(
+            to_hex("0, 81, 81, 30, 0.5", ColorFormat::RawCmyk),
+            ("#b3222280".into(), true)
+        );
+    }
+
+    #[test]
+    fn fail_raw_cmyk_range() {
+        assert!(parse_color("0, 81, 181, 30", ColorFormat::RawCmyk).is_err());
+    }
+
+    #[test]
+    fn cmyk_output() {
+        // Full gray component replacement
+        let gray = Srgba::rgb(0.5, 0.5, 0.5).into();
+        assert_eq!(
+            format_color(gray, ColorFormat::Cmyk, false),
+            "cmyk(0%, 0%, 0%, 50%)"
+        );
+        let red = Srgba::rgb(0.8, 0.2, 0.4).into();
+        assert_eq!(
+            format_color(red, ColorFormat::DeviceCmyk, false),
+            "device-cmyk(0% 75% 50% 20%)"
+        );
+        assert_eq!(
+            format_color(red, ColorFormat::RawCmyk, false),
+            "0, 75, 50, 20"
+        );
+    }
+
+    #[test]
+    fn raw_cmyk_detect() {
+        // Four numbers are read as RGBA unless the format is given
+        assert_eq!(
+            detected("0, 81, 81, 30", &[]),
+            (vec![ColorFormat::RawRgb], false)
+        );
+        assert_eq!(
+            detected("0, 81, 81, 30, 1", &[]),
+            (vec![ColorFormat::RawCmyk], false)
+        );
+    }
+
+    #[test]
+    fn okhsv1() {
+        // Values from https://bottosson.github.io/misc/colorpicker
+        assert_eq!(
+            to_hex("okhsv(29.2 100% 100%)", ColorFormat::Okhsv),
+            ("#f00".into(), true)
+        );
+        assert_eq!(
+            to_hex("okhsv(0 0% 100%)", ColorFormat::Okhsv),
+            ("#fff".into(), true)
+        );
+        assert_eq!(
+            to_hex("251.2, 0.795, 0.34", ColorFormat::RawOkhsv),
+            ("#123456".into(), false)
+        );
+    }
+
+    #[test]
+    fn okhsl1() {
+        assert_eq!(
+            to_hex("okhsl(29.2 100% 56.8%)", ColorFormat::Okhsl),
+            ("#f00".into(), true)
+        );
+        assert_eq!(
+            to_hex("okhsl(251.2 0.691 0.219)", ColorFormat::Okhsl),
+            ("#123456".into(), true)
+        );
+        assert_eq!(
+            to_hex("okhsl(0 0% 0%)", ColorFormat::Okhsl),
+            ("#000".into(), true)
+        );
+    }
 
-fn rgb_parser(input: &mut &str) -> ModalResult<Srgba> {
-    color_read_parser(
-        "rgb".void(),
-        (
-            terminated(css_num_parser.map(|n| n.as_u8()), space1),
-            terminated(css_num_parser.map(|n| n.as_u8()), space1),
-            css_num_parser.map(|n| n.as_u8()),
-            css_alpha_parser,
-        ),
-    )
-    .parse_next(input)
-}
+    #[test]
+    fn fail_okhsl_hue() {
+        assert!(parse_color("400, 0.5, 0.5", ColorFormat::RawOkhsl).is_err());
+    }
 
-fn hsl_parser(input: &mut &str) -> ModalResult<Hsla> {
-    color_read_parser(
-        "hsl".void(),
-        (
-            terminated(css_hue_parser, space1),
-            terminated(css_num_parser.map(|n| n.apply()), space1),
-            css_num_parser.map(|n| n.apply()),
-            css_alpha_parser,
-        ),
-    )
-    .parse_next(input)
-}
+    #[test]
+    fn okhsv_okhsl_output() {
+        let color = Srgba::hex("#123456").unwrap().into();
+        assert_eq!(
+            format_color(color, ColorFormat::Okhsv, false),
+            "okhsv(251.2 79.5% 34%)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::Okhsl, false),
+            "okhsl(251.2 69.1% 21.9%)"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::RawOkhsl, false),
+            "251.17, 0.6915, 0.2189"
+        );
+    }
 
-fn rgb_legacy_parser(input: &mut &str) -> ModalResult<Srgba> {
-    color_read_parser(
-        ("rgb", opt('a')).void(),
-        (
-            terminated(css_legacy_num_parser, (space0, ',', space0)),
-            terminated(css_legacy_num_parser, (space0, ',', space0)),
-            css_legacy_num_parser,
-            css_legacy_alpha_parser,
-        )
-            .verify(|(r, g, b, _)| {
-                matches!(
-                    (r, g, b),
-                    (
-                        CssNum::Percentage(_),
-                        CssNum::Percentage(_),
-                        CssNum::Percentage(_)
-                    ) | (CssNum::Num(_), CssNum::Num(_), CssNum::Num(_))
-                )
-            })
-            .map(|(r, g, b, a)| (r.as_u8(), g.as_u8(), b.as_u8(), a)),
-    )
-    .parse_next(input)
-}
+    #[test]
+    fn okhsv_gray() {
+        let gray = Srgba::rgb_u8(128, 128, 128).into();
+        let text = format_color(gray, ColorFormat::Okhsv, false);
+        assert_eq!(to_hex(&text, ColorFormat::Okhsv), ("#808080".into(), true));
+    }
 
-fn hsl_legacy_parser(input: &mut &str) -> ModalResult<Hsla> {
-    color_read_parser(
-        ("hsl", opt('a')).void(),
-        (
-            terminated(css_legacy_hue_parser, (space0, ',', space0)),
-            terminated(
-                css_percentage_parser.map(|p| p.apply()),
-                (space0, ',', space0),
-            ),
-            css_percentage_parser.map(|p| p.apply()),
-            css_legacy_alpha_parser,
-        ),
-    )
-    .parse_next(input)
-}
+    #[test]
+    fn okhsl_style() {
+        assert_eq!(
+            restyle("okhsl(120deg 0.5 0.5 / 50%)", ColorFormat::Okhsl),
+            "okhsl(120deg 0.5 0.5 / 50%)"
+        );
+    }
 
-#[cfg(test)]
-mod tests {
+    #[test]
+    fn okhsl_hint() {
+        assert_eq!(
+            detected("120, 0.5, 0.5", &[ColorFormat::RawOkhsl]),
+            (vec![ColorFormat::RawOkhsl, ColorFormat::RawOkhsv], false)
+        );
+    }
 
-    use super::*;
+    #[test]
+    fn xyz_output() {
+        let white = Srgba::WHITE.into();
+        assert_eq!(
+            format_color(white, ColorFormat::RawXyz, false),
+            "0.9505, 1.0, 1.0888"
+        );
+        assert_eq!(
+            format_color(white, ColorFormat::RawXyzD50, false),
+            "0.9643, 1.0, 0.8249"
+        );
+    }
 
     #[test]
-    fn js_float() {
-        let res = js_float_parser.parse("1.0");
-        assert_eq!(res, Ok(1.0));
+    fn xyy_output() {
+        assert_eq!(
+            format_color(Srgba::WHITE.into(), ColorFormat::RawXyy, false),
+            "0.3127, 0.329, 1.0"
+        );
+        assert_eq!(
+            format_color(Srgba::BLACK.into(), ColorFormat::RawXyy, false),
+            "0.3127, 0.329, 0.0"
+        );
+        assert_eq!(
+            format_color(Srgba::RED.into(), ColorFormat::RawXyy, true),
+            "0.64, 0.33, 0.2127, 1.0"
+        );
     }
 
     #[test]
-    fn js_float2() {
-        let res = js_float_parser.parse("1");
-        assert_eq!(res, Ok(1.0));
+    fn xyz1() {
+        assert_eq!(
+            to_hex("0.4124, 0.2126, 0.0193", ColorFormat::RawXyz),
+            ("#f00".into(), false)
+        );
+        assert_eq!(
+            to_hex("0.9642, 1.0, 0.8252", ColorFormat::RawXyzD50),
+            ("#fff".into(), false)
+        );
     }
 
     #[test]
-    fn js_float3() {
-        let res = js_float_parser.parse(".1");
-        assert_eq!(res, Ok(0.1));
+    fn xyy1() {
+        assert_eq!(
+            to_hex("0.64, 0.33, 0.2126", ColorFormat::RawXyy),
+            ("#f00".into(), false)
+        );
+        assert!(
+            parse_color("0.3127, 0.329, 0.5, 0.5", ColorFormat::RawXyy)
+                .unwrap()
+                .1
+        );
     }
 
     #[test]
-    fn js_float_fail() {
-        let res = js_float_parser.parse(" 1.0");
-        assert!(matches!(res, Err(..)))
+    fn xyy_not_detected() {
+        assert!(
+            detect_formats("0.64, 0.33, 0.2126", &[])
+                .unwrap()
+                .matches
+                .iter()
+                .all(|m| m.format != ColorFormat::RawXyy)
+        );
     }
 
     #[test]
-    fn js_float_fail2() {
-        let res = js_float_parser.parse("1.0 ");
-        assert!(matches!(res, Err(..)))
+    fn deep_integers_output() {
+        let orange = Srgba::rgb_u8(255, 128, 0).into();
+        assert_eq!(
+            format_color(orange, ColorFormat::RawRgb10, false),
+            "1023, 514, 0"
+        );
+        assert_eq!(
+            format_color(orange, ColorFormat::RawRgb16, true),
+            "65535, 32896, 0, 65535"
+        );
     }
 
     #[test]
-    fn components() {
-        let res = color_components_parser::<Srgba>.parse("1,0.5,1.");
-        let color = Color::from(Srgba::rgb(1., 0.5, 1.));
-        assert_eq!(res, Ok((color, false)));
+    fn deep_integers1() {
+        assert_eq!(
+            to_hex("1023, 514, 0", ColorFormat::RawRgb10),
+            ("#ff8000".into(), false)
+        );
+        assert_eq!(
+            to_hex("65535, 32896, 0, 32896", ColorFormat::RawRgb16),
+            ("#ff800080".into(), true)
+        );
+        assert_eq!(restyle("1023,514,0", ColorFormat::RawRgb10), "1023,514,0");
     }
 
     #[test]
-    fn components2() {
-        let res = color_components_parser::<Srgba>.parse("1, 0.5 , 1. , 0.5");
-        let color = Color::from(Srgba::new(1., 0.5, 1., 0.5));
-        assert_eq!(res, Ok((color, true)));
+    fn fail_deep_integers_range() {
+        assert!(parse_color("1024, 0, 0", ColorFormat::RawRgb10).is_err());
     }
 
     #[test]
-    fn hex1() {
+    fn deep_integers_detect() {
         assert_eq!(
-            parse_color("#aabbcc", ColorFormat::Hex).unwrap(),
-            (Srgba::rgba_u8(170, 187, 204, 255).into(), true)
+            detected("1023, 514, 0", &[]),
+            (vec![ColorFormat::RawRgb10, ColorFormat::RawRgb16], true)
+        );
+        assert_eq!(
+            detected("65535, 32896, 0", &[]),
+            (vec![ColorFormat::RawRgb16], false)
         );
     }
 
     #[test]
-    fn hex2() {
+    fn packed_output() {
+        let orange = Srgba::rgb_u8(255, 128, 0).into();
         assert_eq!(
-            parse_color("#aabbcc00", ColorFormat::Hex).unwrap(),
-            (Srgba::rgba_u8(170, 187, 204, 0).into(), true)
+            format_color(orange, ColorFormat::PackedArgb, false),
+            "16744448"
+        );
+        assert_eq!(
+            format_color(orange, ColorFormat::PackedArgb, true),
+            "4294934528"
+        );
+        assert_eq!(
+            format_color(orange, ColorFormat::PackedRgba, false),
+            "4286578943"
         );
     }
 
     #[test]
-    fn hex3() {
+    fn packed1() {
         assert_eq!(
-            parse_color("#aaa", ColorFormat::Hex).unwrap(),
-            (Srgba::rgba_u8(170, 170, 170, 255).into(), true)
+            to_hex("16777215", ColorFormat::PackedArgb),
+            ("#fff".into(), false)
+        );
+        assert_eq!(
+            to_hex("2164228096", ColorFormat::PackedArgb),
+            ("#ff800080".into(), true)
+        );
+        assert_eq!(
+            to_hex("4286578816", ColorFormat::PackedRgba),
+            ("#ff800080".into(), true)
         );
     }
 
     #[test]
-    fn fail_hex1() {
-        assert_eq!(parse_color("", ColorFormat::Hex), None);
+    fn packed_signed() {
+        // Java's signed Color.getRGB()
+        assert_eq!(
+            to_hex("-32768", ColorFormat::PackedArgb),
+            ("#ff8000".into(), true)
+        );
     }
 
     #[test]
-    fn fail_hex2() {
-        assert_eq!(parse_color("#a", ColorFormat::Hex), None);
+    fn fail_packed_range() {
+        assert!(parse_color("4294967296", ColorFormat::PackedArgb).is_err());
     }
 
     #[test]
-    fn rgb1() {
+    fn packed_not_detected() {
+        // Plain integers are too common to be guessed as colors
+        for s in ["255", "16744448", "4286578816", "-32768"] {
+            assert!(parse_color_unknown_format(s).is_err());
+        }
+        let options = FindOptions {
+            raw: true,
+            named: true,
+        };
+        assert!(found("let width = 255; let id = 16744448;", options).is_empty());
+    }
+
+    #[test]
+    fn reduced_depth_hex_output() {
+        let magenta = Srgba::rgb_u8(255, 0, 255).into();
+        assert_eq!(format_color(magenta, ColorFormat::Rgb565, false), "0xF81F");
+        assert_eq!(format_color(magenta, ColorFormat::Bgr565, false), "0xF81F");
+        assert_eq!(format_color(magenta, ColorFormat::Rgb332, false), "0xE3");
         assert_eq!(
-            parse_color("rgb(170 187 204)", ColorFormat::Rgb).unwrap(),
-            (Srgba::rgba_u8(170, 187, 204, 255).into(), true)
+            format_color(magenta, ColorFormat::Argb4444, false),
+            "0xFF0F"
         );
+        assert_eq!(format_color(magenta, ColorFormat::Rgb444, false), "0xF0F");
     }
 
     #[test]
-    fn rgb2() {
+    fn rgb565() {
         assert_eq!(
-            parse_color("rgb(170 187 204 / 0.0)", ColorFormat::Rgb).unwrap(),
-            (Srgba::rgba_u8(170, 187, 204, 0).into(), true)
+            to_hex("0x07E0", ColorFormat::Rgb565),
+            ("#0f0".into(), false)
         );
+        assert_eq!(to_hex("0x1f", ColorFormat::Rgb565), ("#00f".into(), false));
+        assert_eq!(restyle("0xf81f", ColorFormat::Rgb565), "0xf81f");
     }
 
     #[test]
-    fn rgb3() {
+    fn bgr565() {
         assert_eq!(
-            parse_color("rgb(   170 187 204/.0%  )", ColorFormat::Rgb).unwrap(),
-            (Srgba::rgba_u8(170, 187, 204, 0).into(), true)
+            to_hex("0x001F", ColorFormat::Bgr565),
+            ("#f00".into(), false)
         );
     }
 
     #[test]
-    fn fail_rgb1() {
-        assert_eq!(parse_color("170 187 204", ColorFormat::Rgb), None);
+    fn rgb332() {
+        assert_eq!(to_hex("0x1C", ColorFormat::Rgb332), ("#0f0".into(), false));
     }
 
     #[test]
-    fn fail_rgb2() {
-        assert_eq!(parse_color("rgb(1 2)", ColorFormat::Rgb), None);
+    fn argb4444() {
+        assert_eq!(
+            to_hex("0x8F00", ColorFormat::Argb4444),
+            ("#f008".into(), true)
+        );
     }
+
     #[test]
-    fn fail_rgb3() {
-        assert_eq!(parse_color("rgb()", ColorFormat::Rgb), None);
+    fn fail_reduced_depth_hex_width() {
+        assert!(parse_color("0x1000", ColorFormat::Rgb444).is_err());
+        assert!(parse_color("0x100", ColorFormat::Rgb332).is_err());
     }
 
     #[test]
-    fn fail_rgb4() {
-        assert_eq!(parse_color("rgb(x 1 1%)", ColorFormat::Rgb), None);
+    fn reduced_depth_hex_quantize() {
+        // Quantized to 5 bits of red, 6 of green and 5 of blue
+        let orange = Srgba::rgb_u8(255, 128, 0).into();
+        let quantized = Srgba::from(ColorFormat::Rgb565.quantize(orange)).to_u8_array();
+        assert_eq!(quantized, [255, 130, 0, 255]);
+        assert_eq!(ColorFormat::Hex.quantize(orange), orange);
     }
 
     #[test]
-    fn rgb_legacy() {
+    fn reduced_depth_hex_not_detected() {
+        for s in ["0xF81F", "0xE3", "0xF0F"] {
+            assert!(parse_color_unknown_format(s).is_err());
+        }
+    }
+
+    #[test]
+    fn hex_byte_orders_output() {
+        let color = Srgba::rgba_u8(0x11, 0x22, 0x33, 0x80).into();
+        assert_eq!(format_color(color, ColorFormat::HexArgb, true), "#80112233");
+        assert_eq!(format_color(color, ColorFormat::HexAbgr, true), "#80332211");
         assert_eq!(
-            parse_color("rgba(255, 255, 255, 0.5)", ColorFormat::RgbLegacy).unwrap(),
-            (Srgba::new(1., 1., 1., 0.5).into(), true)
+            format_color(color, ColorFormat::HexLiteralRgba, true),
+            "0x11223380"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::HexLiteralAbgr, true),
+            "0x80332211"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::HexLiteralAbgr, false),
+            "0x332211"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::Colorref, true),
+            "0x00332211"
         );
     }
 
     #[test]
-    fn fail_rgb_legacy_mixed_units() {
-        assert_eq!(parse_color("rgb(1.0%, 1, 1)", ColorFormat::RgbLegacy), None);
+    fn hex_byte_orders_short() {
+        let color = Srgba::rgb_u8(0x11, 0x22, 0x33).into();
+        assert_eq!(format_color(color, ColorFormat::HexAbgr, false), "#321");
+        assert_eq!(format_color(color, ColorFormat::HexArgb, false), "#123");
     }
 
     #[test]
-    fn hsl_legacy() {
+    fn hex_argb() {
         assert_eq!(
-            parse_color("hsla(50, 10%, 10%, 0.5)", ColorFormat::HslLegacy).unwrap(),
-            (Hsla::new(50., 0.1, 0.1, 0.5).into(), true)
+            to_hex("#80112233", ColorFormat::HexArgb),
+            ("#11223380".into(), true)
+        );
+        assert_eq!(
+            to_hex("#8123", ColorFormat::HexArgb),
+            ("#1238".into(), true)
         );
     }
 
     #[test]
-    fn oklch1() {
+    fn hex_abgr() {
         assert_eq!(
-            parse_color("oklch(0.5 0.4 0.2)", ColorFormat::Oklch).unwrap(),
-            (Oklcha::new(0.5, 0.4, 0.2, 1.).into(), true)
+            to_hex("#332211", ColorFormat::HexAbgr),
+            ("#123".into(), true)
         );
+        assert_eq!(restyle("#AbCdEf", ColorFormat::HexAbgr), "#ABCDEF");
     }
 
     #[test]
-    fn oklch2() {
+    fn hex_literal_byte_orders() {
         assert_eq!(
-            parse_color("oklch( 10% 100% 150 / 20% )", ColorFormat::Oklch).unwrap(),
-            (Oklcha::new(0.1, 0.4, 150., 0.2).into(), true)
+            to_hex("0x11223380", ColorFormat::HexLiteralRgba),
+            ("#11223380".into(), true)
+        );
+        assert_eq!(
+            to_hex("0x80332211", ColorFormat::HexLiteralAbgr),
+            ("#11223380".into(), true)
         );
     }
 
     #[test]
-    fn raw_rgb_float1() {
+    fn colorref() {
+        // The high byte of a COLORREF isn't alpha
         assert_eq!(
-            parse_color("0,.1,1.,0.2", ColorFormat::RawRgbFloat).unwrap(),
-            (Srgba::new(0.0, 0.1, 1.0, 0.2).into(), true)
+            to_hex("0x00332211", ColorFormat::Colorref),
+            ("#123".into(), false)
+        );
+        assert_eq!(
+            to_hex("0x332211", ColorFormat::Colorref),
+            ("#123".into(), false)
         );
+        assert_eq!(restyle("0x00ffeedd", ColorFormat::Colorref), "0x00ffeedd");
     }
 
     #[test]
-    fn raw_rgb_float2() {
+    fn hex_byte_orders_not_detected() {
+        // Nothing tells the byte orders apart
+        assert_eq!(detected("#80112233", &[]), (vec![ColorFormat::Hex], false));
+    }
+
+    #[test]
+    fn latex_output() {
+        let color = Srgba::rgb_u8(0xaa, 0xbb, 0xcc).into();
         assert_eq!(
-            parse_color("0,.1,1.", ColorFormat::RawRgbFloat).unwrap(),
-            (Srgba::new(0.0, 0.1, 1.0, 1.0).into(), false)
+            format_color(color, ColorFormat::LatexHtml, false),
+            r"\color[HTML]{AABBCC}"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::LatexRgb, false),
+            r"\color[rgb]{0.6667,0.7333,0.8}"
+        );
+        assert_eq!(
+            format_color(color, ColorFormat::LatexRgb8, false),
+            r"\color[RGB]{170,187,204}"
         );
     }
 
     #[test]
-    fn raw_rgb_float3() {
+    fn latex1() {
         assert_eq!(
-            parse_color("0.0,     0.5,   0.8", ColorFormat::RawRgbFloat).unwrap(),
-            (Srgba::new(0.0, 0.5, 0.8, 1.0).into(), false)
+            to_hex(
+                r"\definecolor{accent}{HTML}{AABBCC}",
+                ColorFormat::LatexHtml
+            ),
+            ("#abc".into(), false)
+        );
+        assert_eq!(
+            to_hex("{rgb}{1, 0.5, 0}", ColorFormat::LatexRgb),
+            ("#ff8000".into(), false)
+        );
+        assert_eq!(
+            to_hex(r"\color[RGB]{10,20,30}", ColorFormat::LatexRgb8),
+            ("#0a141e".into(), false)
         );
     }
 
     #[test]
-    fn fail_raw_rgb_float1() {
-        assert_eq!(parse_color("0.0 0.5, 0.8", ColorFormat::RawRgbFloat), None);
+    fn fail_latex_model() {
+        assert!(parse_color(r"\color[rgb]{10,20,30}", ColorFormat::LatexRgb).is_err());
+        assert!(parse_color(r"\color[RGB]{AABBCC}", ColorFormat::LatexHtml).is_err());
     }
 
     #[test]
-    fn fail_raw_rgb_float2() {
-        assert_eq!(parse_color("0", ColorFormat::RawRgbFloat), None);
+    fn latex_style() {
+        assert_eq!(
+            restyle(
+                r"\definecolor{accent}{HTML}{aabbcc}",
+                ColorFormat::LatexHtml
+            ),
+            r"\definecolor{accent}{HTML}{aabbcc}"
+        );
+        assert_eq!(
+            restyle(r"\textcolor[rgb]{0.1, 0.2, 0.3}", ColorFormat::LatexRgb),
+            r"\textcolor[rgb]{0.1, 0.2, 0.3}"
+        );
     }
 
     #[test]
-    fn fail_raw_rgb_float3() {
-        assert_eq!(parse_color("0, 0", ColorFormat::RawRgbFloat), None);
+    fn typst_hex() {
+        assert_eq!(
+            format_color(
+                Srgba::rgb_u8(0xaa, 0xbb, 0xcc).into(),
+                ColorFormat::TypstHex,
+                false
+            ),
+            "rgb(\"#abc\")"
+        );
+        assert_eq!(
+            to_hex("rgb(\"#aabbcc80\")", ColorFormat::TypstHex),
+            ("#aabbcc80".into(), true)
+        );
     }
 
     #[test]
-    fn fail_raw_rgb_float4() {
-        assert_eq!(parse_color("0, 0, 0, 0, 0", ColorFormat::RawRgbFloat), None);
+    fn typst_luma() {
+        assert_eq!(
+            to_hex("luma(50%)", ColorFormat::TypstLuma),
+            ("#808080".into(), true)
+        );
+        assert_eq!(
+            to_hex("luma(128, 50%)", ColorFormat::TypstLuma),
+            ("#80808080".into(), true)
+        );
+        assert_eq!(restyle("luma(50%)", ColorFormat::TypstLuma), "luma(50%)");
+        assert_eq!(restyle("luma(128)", ColorFormat::TypstLuma), "luma(128)");
     }
 
     #[test]
-    fn raw_hex_literal() {
+    fn typst_oklch() {
         assert_eq!(
-            parse_color("0x001122", ColorFormat::HexLiteral),
-            Some((Srgba::rgb_u8(0, 17, 34).into(), false))
+            to_hex("oklch(62.8%, 0.2577, 29.23deg)", ColorFormat::TypstOklch),
+            ("#f00".into(), true)
+        );
+        assert_eq!(
+            restyle("oklch(50%, 0.1, 120deg, 50%)", ColorFormat::TypstOklch),
+            "oklch(50%, 0.1, 120deg, 50%)"
         );
     }
 
     #[test]
-    fn raw_hex_literal_alpha() {
+    fn typst_oklch_hue_unit() {
+        // Typst only knows degrees and radians
+        let turn = FormatOptions {
+            hue_unit: Some(HueUnit::Turn),
+            ..Default::default()
+        };
         assert_eq!(
-            parse_color("0x33001122", ColorFormat::HexLiteral),
-            Some((Srgba::rgba_u8(0, 17, 34, 51).into(), true))
+            format_with("oklch(50%, 0.1, 120deg)", ColorFormat::TypstOklch, turn),
+            "oklch(50%, 0.1, 120deg)"
+        );
+    }
+
+    #[test]
+    fn latex_and_typst_detect() {
+        assert_eq!(
+            detected(r"\color[HTML]{AABBCC}", &[]),
+            (vec![ColorFormat::LatexHtml], false)
+        );
+        assert_eq!(
+            detected("rgb(\"#abc\")", &[]),
+            (vec![ColorFormat::TypstHex], false)
+        );
+        assert_eq!(
+            detected("oklch(50%, 0.1, 120deg)", &[]),
+            (vec![ColorFormat::TypstOklch], false)
+        );
+        assert_eq!(
+            detected("luma(128)", &[]),
+            (vec![ColorFormat::TypstLuma], false)
+        );
+        assert_eq!(
+            found(
+                r"\textcolor[HTML]{AABBCC}{text} and #set text(fill: luma(20%))",
+                FindOptions::default()
+            ),
+            vec![
+                (r"\textcolor[HTML]{AABBCC}", ColorFormat::LatexHtml),
+                ("luma(20%)", ColorFormat::TypstLuma)
+            ]
         );
     }
 }
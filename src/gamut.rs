@@ -4,9 +4,9 @@
 
 #![allow(non_upper_case_globals)]
 
-use std::f32::consts::PI;
+use std::{f32::consts::PI, sync::LazyLock};
 
-use bevy_color::{LinearRgba, Oklaba, Oklcha};
+use bevy_color::{ColorToPacked, LinearRgba, Oklaba, Oklcha, Srgba};
 
 #[allow(clippy::excessive_precision)]
 pub fn compute_max_saturation(a: f32, b: f32) -> f32 {
@@ -231,6 +231,55 @@ pub fn clamp_rgba(rgba: LinearRgba) -> LinearRgba {
     }
 }
 
+/// deltaEOK, the Euclidean distance of two colors in Oklab
+pub fn delta_eok(a: Oklaba, b: Oklaba) -> f32 {
+    let (dl, da, db) = (a.lightness - b.lightness, a.a - b.a, a.b - b.b);
+    (dl * dl + da * da + db * db).sqrt()
+}
+
+/// Default xterm colors 0 to 15. Terminal themes usually change these.
+const XTERM_SYSTEM_COLORS: [u32; 16] = [
+    0x000000, 0xcd0000, 0x00cd00, 0xcdcd00, 0x0000ee, 0xcd00cd, 0x00cdcd, 0xe5e5e5, 0x7f7f7f,
+    0xff0000, 0x00ff00, 0xffff00, 0x5c5cff, 0xff00ff, 0x00ffff, 0xffffff,
+];
+
+/// The color of an xterm-256 palette index: 16 system colors, a 6x6x6 cube and 24 grays
+pub fn xterm_color(index: u8) -> Srgba {
+    let level = |i: u8| if i == 0 { 0 } else { 55 + 40 * i };
+    match index {
+        0..16 => {
+            let [_, r, g, b] = XTERM_SYSTEM_COLORS[index as usize].to_be_bytes();
+            Srgba::rgb_u8(r, g, b)
+        }
+        16..232 => {
+            let i = index - 16;
+            Srgba::rgb_u8(level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + 10 * (index - 232);
+            Srgba::rgb_u8(v, v, v)
+        }
+    }
+}
+
+static XTERM_OKLAB: LazyLock<Vec<Oklaba>> =
+    LazyLock::new(|| (16..=255).map(|i| xterm_color(i).into()).collect());
+
+/// Finds the xterm-256 palette index closest to `color` by deltaEOK, and the distance.
+/// The system colors are skipped since they depend on the terminal theme.
+pub fn nearest_xterm_color(color: LinearRgba) -> (u8, f32) {
+    // Compare quantized colors so exact matches really have zero distance
+    let [r, g, b] = Srgba::from(color).to_u8_array_no_alpha();
+    let oklab = Oklaba::from(Srgba::rgb_u8(r, g, b));
+
+    XTERM_OKLAB
+        .iter()
+        .zip(16..=255)
+        .map(|(xterm, i)| (i, delta_eok(oklab, *xterm)))
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap()
+}
+
 const K1: f32 = 0.206;
 const K2: f32 = 0.03;
 const K3: f32 = (1. + K1) / (1. + K2);
@@ -371,7 +420,8 @@ impl From<Oklaba> for Okhsva {
     fn from(oklaba: Oklaba) -> Self {
         let c = (oklaba.a * oklaba.a + oklaba.b * oklaba.b).sqrt();
         if c == 0. {
-            return Okhsva::new(0., 0., 0., oklaba.alpha);
+            // Grays have no hue, and their value is just the lightness with the toe
+            return Okhsva::new(0., 0., toe(oklaba.lightness), oklaba.alpha);
         }
 
         let a_ = oklaba.a / c;
@@ -441,3 +491,177 @@ impl From<Okhsva> for Oklrcha {
         Oklrcha::from(Oklcha::from(Oklaba::from(okhsv)))
     }
 }
+
+/// Approximation of the S and T of the middle of the gamut triangle, for a normalized hue
+#[allow(clippy::excessive_precision)]
+fn get_st_mid(a_: f32, b_: f32) -> (f32, f32) {
+    let s = 0.11516993
+        + 1. / (7.44778970
+            + 4.15901240 * b_
+            + a_ * (-2.19557347
+                + 1.75198401 * b_
+                + a_ * (-2.13704948 - 10.02301043 * b_
+                    + a_ * (-4.24894561 + 5.38770819 * b_ + 4.69891013 * a_))));
+
+    let t = 0.11239642
+        + 1. / (1.61320320 - 0.68124379 * b_
+            + a_ * (0.40370612
+                + 0.90148123 * b_
+                + a_ * (-0.27087943
+                    + 0.61223990 * b_
+                    + a_ * (0.00299215 - 0.45399568 * b_ - 0.14661872 * a_))));
+
+    (s, t)
+}
+
+/// The chroma at saturation 0, 0.8 and 1 of Okhsl for a lightness and normalized hue
+fn get_cs(l: f32, a_: f32, b_: f32) -> (f32, f32, f32) {
+    let cusp = find_cusp(a_, b_);
+
+    let c_max = find_gamut_intersection(a_, b_, l, 1., l);
+    let (s_max, t_max) = to_st(cusp);
+
+    // Scale factor to compensate for the curved part of gamut shape:
+    let k = c_max / (l * s_max).min((1. - l) * t_max);
+
+    let c_mid = {
+        let (s_mid, t_mid) = get_st_mid(a_, b_);
+
+        // Use a soft minimum function, instead of a sharp triangle shape to get a smooth value for chroma.
+        let c_a = l * s_mid;
+        let c_b = (1. - l) * t_mid;
+        0.9 * k * (1. / (1. / c_a.powi(4) + 1. / c_b.powi(4))).sqrt().sqrt()
+    };
+
+    let c_0 = {
+        // for C_0, the shape is independent of hue, so ST are constant. Values picked to roughly be the average values of ST.
+        let c_a = l * 0.4;
+        let c_b = (1. - l) * 0.8;
+        (1. / (1. / (c_a * c_a) + 1. / (c_b * c_b))).sqrt()
+    };
+
+    (c_0, c_mid, c_max)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Okhsla {
+    pub hue: f32,
+    pub saturation: f32,
+    pub lightness: f32,
+    pub alpha: f32,
+}
+
+impl Okhsla {
+    pub fn new(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Self {
+        Self {
+            hue,
+            saturation,
+            lightness,
+            alpha,
+        }
+    }
+}
+
+impl From<Okhsla> for Oklaba {
+    fn from(okhsl: Okhsla) -> Self {
+        let h = okhsl.hue / 360.;
+        let s = okhsl.saturation;
+        let l = okhsl.lightness;
+
+        if l >= 1. {
+            return Oklaba::new(1., 0., 0., okhsl.alpha);
+        } else if l <= 0. {
+            return Oklaba::new(0., 0., 0., okhsl.alpha);
+        }
+
+        let a_ = (2. * PI * h).cos();
+        let b_ = (2. * PI * h).sin();
+        let ll = toe_inv(l);
+
+        let (c_0, c_mid, c_max) = get_cs(ll, a_, b_);
+
+        let mid = 0.8;
+        let mid_inv = 1.25;
+
+        let c = if s < mid {
+            let t = mid_inv * s;
+
+            let k_1 = mid * c_0;
+            let k_2 = 1. - k_1 / c_mid;
+
+            t * k_1 / (1. - k_2 * t)
+        } else {
+            let t = (s - mid) / (1. - mid);
+
+            let k_0 = c_mid;
+            let k_1 = (1. - mid) * c_mid * c_mid * mid_inv * mid_inv / c_0;
+            let k_2 = 1. - k_1 / (c_max - c_mid);
+
+            k_0 + t * k_1 / (1. - k_2 * t)
+        };
+
+        Oklaba::new(ll, c * a_, c * b_, okhsl.alpha)
+    }
+}
+
+impl From<Oklaba> for Okhsla {
+    fn from(oklaba: Oklaba) -> Self {
+        let c = (oklaba.a * oklaba.a + oklaba.b * oklaba.b).sqrt();
+        let l = toe(oklaba.lightness);
+        if c == 0. || l <= 0. || l >= 1. {
+            return Okhsla::new(0., 0., l.clamp(0., 1.), oklaba.alpha);
+        }
+
+        let a_ = oklaba.a / c;
+        let b_ = oklaba.b / c;
+
+        let h = 0.5 + 0.5 * (-oklaba.b).atan2(-oklaba.a) / PI;
+
+        let (c_0, c_mid, c_max) = get_cs(oklaba.lightness, a_, b_);
+
+        // Inverse of the interpolation in `From<Okhsla> for Oklaba`
+        let mid = 0.8;
+        let mid_inv = 1.25;
+
+        let s = if c < c_mid {
+            let k_1 = mid * c_0;
+            let k_2 = 1. - k_1 / c_mid;
+
+            let t = c / (k_1 + k_2 * c);
+            t * mid
+        } else {
+            let k_0 = c_mid;
+            let k_1 = (1. - mid) * c_mid * c_mid * mid_inv * mid_inv / c_0;
+            let k_2 = 1. - k_1 / (c_max - c_mid);
+
+            let t = (c - k_0) / (k_1 + k_2 * (c - k_0));
+            mid + (1. - mid) * t
+        };
+
+        Okhsla::new(h * 360., s, l, oklaba.alpha)
+    }
+}
+
+impl From<Okhsla> for LinearRgba {
+    fn from(okhsl: Okhsla) -> Self {
+        Oklaba::from(okhsl).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xterm_nearest() {
+        for i in 16..=255 {
+            assert_eq!(nearest_xterm_color(xterm_color(i).into()), (i, 0.));
+        }
+        // System colors depend on the theme and are never picked
+        assert_eq!(nearest_xterm_color(Srgba::rgb_u8(0, 0, 0).into()).0, 16);
+        assert_eq!(
+            nearest_xterm_color(Srgba::rgb_u8(250, 100, 70).into()).0,
+            203
+        );
+    }
+}
@@ -1,7 +1,7 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use bevy_color::{Color, Oklcha};
-use formats::ColorFormat;
+use formats::{ColorFormat, ColorStyle};
 use gamut::gamut_clip_preserve_chroma;
 use rand::prelude::*;
 #[cfg(not(target_arch = "wasm32"))]
@@ -13,41 +13,86 @@ mod cli;
 mod formats;
 mod gamut;
 mod gl_programs;
+mod mix;
+mod named_colors;
+mod spaces;
+mod templates;
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> ExitCode {
     use clap::Parser as _;
     use cli::Cli;
     use egui::{Vec2, ViewportBuilder};
-    use formats::{parse_color, parse_color_unknown_format};
+    use formats::{FormatMatch, detect_formats, parse_color};
 
     log_startup::init();
 
+    // The user formats are needed to parse the format arguments. A broken default config
+    // shouldn't make the picker unusable, so only an explicitly given file is an error.
+    if let Err(e) = templates::load_templates() {
+        eprintln!("Could not load user formats: {e}");
+        if e.explicit {
+            return ExitCode::FAILURE;
+        }
+    }
+
     let cli = Cli::parse();
 
     log_startup::log("Cli parse");
 
-    let (color, format, use_alpha) = match (cli.color, cli.format) {
+    let format_options = cli.format_options();
+
+    let (color, format, use_alpha, style) = match (cli.color, cli.format) {
         (Some(color_string), Some(format)) => {
-            let Some((color, use_alpha)) = parse_color(&color_string, format) else {
-                eprintln!(
-                    "Invalid color '{}' for specified format '{}'",
-                    color_string, format
-                );
-                return ExitCode::FAILURE;
+            let (color, use_alpha) = match parse_color(&color_string, format) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!(
+                        "Invalid color '{}' for specified format '{}'",
+                        color_string, format
+                    );
+                    print_parse_error(&color_string, &e);
+                    return ExitCode::FAILURE;
+                }
             };
 
-            (color, format, use_alpha)
+            let style = ColorStyle::detect(&color_string, format);
+            (color, format, use_alpha, style)
         }
         (Some(color_string), None) => {
-            let Some((color, format, use_alpha)) = parse_color_unknown_format(&color_string) else {
-                eprintln!("Could not detect format for color '{}'", color_string);
-                return ExitCode::FAILURE;
+            let detection = match detect_formats(&color_string, &cli.format_hint) {
+                Ok(detection) => detection,
+                Err(e) => {
+                    eprintln!("Could not parse color '{}'", color_string);
+                    print_parse_error(&color_string, &e);
+                    return ExitCode::FAILURE;
+                }
             };
-            (color, format, use_alpha)
+            let &FormatMatch {
+                color,
+                format,
+                use_alpha,
+                ..
+            } = detection.best();
+            if detection.ambiguous {
+                let likely: Vec<_> = detection.likely().map(|m| m.format.to_string()).collect();
+                eprintln!(
+                    "Color '{}' is ambiguous between {}, using '{}' (see --format-hint)",
+                    color_string,
+                    likely.join(", "),
+                    format
+                );
+            }
+            let style = ColorStyle::detect(&color_string, format);
+            (color, format, use_alpha, style)
         }
-        (None, Some(format)) => (random_color(), format, true),
-        (None, None) => (random_color(), ColorFormat::default(), true),
+        (None, Some(format)) => (random_color(), format, true, ColorStyle::default()),
+        (None, None) => (
+            random_color(),
+            ColorFormat::default(),
+            true,
+            ColorStyle::default(),
+        ),
     };
     log_startup::log("Color parse");
 
@@ -59,7 +104,7 @@ fn main() -> ExitCode {
         ..Default::default()
     };
 
-    let data = Arc::new((color, format, use_alpha));
+    let data = Arc::new((color, format, use_alpha, style, format_options));
 
     eframe::run_native(
         "Oklch Color Picker",
@@ -71,6 +116,16 @@ fn main() -> ExitCode {
     ExitCode::SUCCESS
 }
 
+/// Prints the error with the failing part of the input underlined
+#[cfg(not(target_arch = "wasm32"))]
+fn print_parse_error(input: &str, error: &formats::ParseError) {
+    let start = input[..error.span.start].chars().count();
+    let len = input[error.span.clone()].chars().count().max(1);
+    eprintln!("{error}");
+    eprintln!("  {input}");
+    eprintln!("  {}{}", " ".repeat(start), "^".repeat(len));
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn load_icon() -> egui::IconData {
     let icon = include_bytes!("../assets/icon.png");
@@ -87,6 +142,7 @@ fn load_icon() -> egui::IconData {
 #[cfg(target_arch = "wasm32")]
 fn main() {
     use eframe::wasm_bindgen::JsCast as _;
+    use formats::FormatOptions;
 
     let web_options = eframe::WebOptions::default();
 
@@ -102,7 +158,13 @@ fn main() {
             .dyn_into::<web_sys::HtmlCanvasElement>()
             .expect("the_canvas_id was not a HtmlCanvasElement");
 
-        let data = Arc::new((random_color(), ColorFormat::default(), true));
+        let data = Arc::new((
+            random_color(),
+            ColorFormat::default(),
+            true,
+            ColorStyle::default(),
+            FormatOptions::default(),
+        ));
 
         let start_result = eframe::WebRunner::new()
             .start(
@@ -0,0 +1,170 @@
+//! CSS `color-mix()` evaluation
+//! https://www.w3.org/TR/css-color-5/#color-mix
+
+use bevy_color::{ColorToComponents, Hsla, Hwba, LinearRgba, Oklaba, Oklcha};
+
+use crate::spaces::{CieLaba, CieLcha, CssColorSpace};
+
+/// Chroma below this, relative to the chroma range, makes the hue powerless
+const ACHROMATIC_EPSILON: f32 = 4e-4;
+
+/// Interpolation spaces of `color-mix()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MixSpace {
+    Rectangular(CssColorSpace),
+    Lab,
+    Oklab,
+    Hsl,
+    Hwb,
+    Lch,
+    Oklch,
+}
+
+impl MixSpace {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "lab" => Self::Lab,
+            "oklab" => Self::Oklab,
+            "hsl" => Self::Hsl,
+            "hwb" => Self::Hwb,
+            "lch" => Self::Lch,
+            "oklch" => Self::Oklch,
+            _ => Self::Rectangular(name.parse().ok()?),
+        })
+    }
+
+    pub fn hue_index(self) -> Option<usize> {
+        match self {
+            Self::Hsl | Self::Hwb => Some(0),
+            Self::Lch | Self::Oklch => Some(2),
+            _ => None,
+        }
+    }
+
+    pub fn to_values(self, color: LinearRgba) -> [f32; 4] {
+        match self {
+            Self::Rectangular(space) => space.to_values(color),
+            Self::Lab => {
+                let c = CieLaba::from(color);
+                [c.lightness, c.a, c.b, c.alpha]
+            }
+            Self::Oklab => Oklaba::from(color).to_f32_array(),
+            Self::Hsl => Hsla::from(color).to_f32_array(),
+            Self::Hwb => Hwba::from(color).to_f32_array(),
+            Self::Lch => {
+                let c = CieLcha::from(color);
+                [c.lightness, c.chroma, c.hue, c.alpha]
+            }
+            Self::Oklch => Oklcha::from(color).to_f32_array(),
+        }
+    }
+
+    pub fn to_color(self, [c1, c2, c3, alpha]: [f32; 4]) -> LinearRgba {
+        match self {
+            Self::Rectangular(space) => space.to_linear_rgba([c1, c2, c3, alpha]),
+            Self::Lab => CieLaba::new(c1, c2, c3, alpha).into(),
+            Self::Oklab => Oklaba::new(c1, c2, c3, alpha).into(),
+            Self::Hsl => Hsla::new(c1, c2, c3, alpha).into(),
+            Self::Hwb => Hwba::new(c1, c2, c3, alpha).into(),
+            Self::Lch => CieLcha::new(c1, c2, c3, alpha).into(),
+            Self::Oklch => Oklcha::new(c1, c2, c3, alpha).into(),
+        }
+    }
+
+    /// Whether the hue doesn't affect the color and should be taken from the other color
+    fn is_achromatic(self, values: [f32; 4]) -> bool {
+        match self {
+            Self::Hsl => values[1] < ACHROMATIC_EPSILON,
+            Self::Lch => values[1] < ACHROMATIC_EPSILON * 150.,
+            Self::Oklch => values[1] < ACHROMATIC_EPSILON * 0.4,
+            Self::Hwb => values[1] + values[2] >= 1. - ACHROMATIC_EPSILON,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, strum::EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum HueInterpolation {
+    #[default]
+    Shorter,
+    Longer,
+    Increasing,
+    Decreasing,
+}
+
+impl HueInterpolation {
+    /// Adjusts the hues so that linear interpolation between them takes the wanted arc
+    fn fixup(self, mut h1: f32, mut h2: f32) -> (f32, f32) {
+        let diff = h2 - h1;
+        match self {
+            Self::Shorter => {
+                if diff > 180. {
+                    h1 += 360.;
+                } else if diff < -180. {
+                    h2 += 360.;
+                }
+            }
+            Self::Longer => {
+                if 0. < diff && diff < 180. {
+                    h1 += 360.;
+                } else if -180. < diff && diff <= 0. {
+                    h2 += 360.;
+                }
+            }
+            Self::Increasing => {
+                if h2 < h1 {
+                    h2 += 360.;
+                }
+            }
+            Self::Decreasing => {
+                if h1 < h2 {
+                    h1 += 360.;
+                }
+            }
+        }
+        (h1, h2)
+    }
+}
+
+/// Interpolates from `c1` to `c2` by `t` with premultiplied alpha
+pub fn mix(
+    space: MixSpace,
+    hue_interpolation: HueInterpolation,
+    c1: LinearRgba,
+    c2: LinearRgba,
+    t: f32,
+) -> LinearRgba {
+    let mut v1 = space.to_values(c1);
+    let mut v2 = space.to_values(c2);
+    let hue_index = space.hue_index();
+
+    if let Some(h) = hue_index {
+        match (space.is_achromatic(v1), space.is_achromatic(v2)) {
+            (true, false) => v1[h] = v2[h],
+            (false, true) => v2[h] = v1[h],
+            _ => {}
+        }
+        (v1[h], v2[h]) = hue_interpolation.fixup(v1[h], v2[h]);
+    }
+
+    let lerp = |a: f32, b: f32| a * (1. - t) + b * t;
+    let (a1, a2) = (v1[3], v2[3]);
+    let alpha = lerp(a1, a2);
+
+    let mut values = [0., 0., 0., alpha];
+    for (i, value) in values.iter_mut().take(3).enumerate() {
+        *value = if Some(i) == hue_index {
+            lerp(v1[i], v2[i]).rem_euclid(360.)
+        } else {
+            let premultiplied = lerp(v1[i] * a1, v2[i] * a2);
+            if alpha == 0. {
+                premultiplied
+            } else {
+                premultiplied / alpha
+            }
+        };
+    }
+
+    space.to_color(values)
+}
@@ -1,11 +1,14 @@
 mod formats;
 mod gamut;
+mod mix;
+mod named_colors;
+mod spaces;
+mod templates;
 
 #[cfg(not(target_arch = "wasm32"))]
 mod lua {
     use super::*;
     use bevy_color::{Color, ColorToPacked, Srgba};
-    use clap::ValueEnum;
     use formats::ColorFormat;
     use mlua::prelude::*;
 
@@ -23,30 +26,80 @@ mod lua {
 
     fn parse(_: &Lua, (color, fmt): (String, Option<String>)) -> LuaResult<Option<u32>> {
         let color = if let Some(fmt) = fmt {
-            let parsed_fmt = ColorFormat::from_str(&fmt, true).map_err(LuaError::RuntimeError)?;
+            let parsed_fmt = ColorFormat::from_name(&fmt)
+                .ok_or_else(|| LuaError::RuntimeError(format!("unknown format `{fmt}`")))?;
             match formats::parse_color(&color, parsed_fmt) {
-                Some((c, _)) => c,
-                None => return Ok(None),
+                Ok((c, _)) => c,
+                Err(_) => return Ok(None),
             }
         } else {
             match formats::parse_color_unknown_format(&color) {
-                Some((c, _, _)) => c,
-                None => return Ok(None),
+                Ok((c, _, _)) => c,
+                Err(_) => return Ok(None),
             }
         };
 
+        Ok(Some(to_rgb_u32(color)))
+    }
+
+    fn to_rgb_u32(color: Color) -> u32 {
         let color = gamut_clip(color);
 
         let srgb = Srgba::from(color);
         let [r, g, b] = srgb.to_u8_array_no_alpha();
 
-        Ok(Some(((r as u32) << 16) | ((g as u32) << 8) | b as u32))
+        ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+    }
+
+    /// Returns a list of `{ start, finish, color, format }` with 1-based inclusive byte indices
+    fn find_colors(lua: &Lua, (text, opts): (String, Option<LuaTable>)) -> LuaResult<LuaTable> {
+        let options = match opts {
+            Some(opts) => formats::FindOptions {
+                raw: opts.get::<Option<bool>>("raw")?.unwrap_or(false),
+                named: opts.get::<Option<bool>>("named")?.unwrap_or(false),
+            },
+            None => formats::FindOptions::default(),
+        };
+
+        let colors = formats::find_colors_with(&text, options)
+            .into_iter()
+            .map(|(range, color, format, _)| {
+                let found = lua.create_table()?;
+                found.set("start", range.start + 1)?;
+                found.set("finish", range.end)?;
+                found.set("color", to_rgb_u32(color))?;
+                found.set("format", format.to_string())?;
+                Ok(found)
+            })
+            .collect::<LuaResult<Vec<_>>>()?;
+        lua.create_sequence_from(colors)
+    }
+
+    /// Shows a warning with `vim.notify` in Neovim, or prints it elsewhere
+    fn warn(lua: &Lua, message: &str) {
+        let notify = lua
+            .globals()
+            .get::<LuaTable>("vim")
+            .and_then(|vim| vim.get::<LuaFunction>("notify"));
+        // `vim.log.levels.WARN`
+        if notify
+            .and_then(|notify| notify.call::<()>((message, 3)))
+            .is_err()
+        {
+            eprintln!("{message}");
+        }
     }
 
     #[mlua::lua_module(skip_memory_check)]
     fn parser_lua_module(lua: &Lua) -> LuaResult<LuaTable> {
+        // A broken config shouldn't break the plugin, it just goes without user formats
+        if let Err(e) = templates::load_templates() {
+            warn(lua, &format!("Could not load user formats: {e}"));
+        }
+
         let exports = lua.create_table()?;
         exports.set("parse", lua.create_function(parse)?)?;
+        exports.set("find_colors", lua.create_function(find_colors)?)?;
         exports.set("version", lua.create_function(version)?)?;
         Ok(exports)
     }
@@ -0,0 +1,510 @@
+//! Color spaces used by CSS that bevy_color doesn't provide.
+//! Matrices and constants are from the CSS Color 4 sample code
+//! https://www.w3.org/TR/css-color-4/#color-conversion-code
+
+#![allow(clippy::excessive_precision)]
+
+use bevy_color::{ColorToComponents, LinearRgba, Srgba, Xyza};
+
+type Mat3 = [[f32; 3]; 3];
+
+fn mul(m: &Mat3, [x, y, z]: [f32; 3]) -> [f32; 3] {
+    m.map(|row| row[0] * x + row[1] * y + row[2] * z)
+}
+
+/// Bradford chromatic adaptation from D65 to D50
+const D65_TO_D50: Mat3 = [
+    [1.047929793, 0.0229468706, -0.05019226629],
+    [0.02962780877, 0.9904344268, -0.01707379906],
+    [-0.009243040646, 0.01505519149, 0.7518742814],
+];
+
+/// Bradford chromatic adaptation from D50 to D65
+const D50_TO_D65: Mat3 = [
+    [0.9554734215, -0.02309845495, 0.0632592432],
+    [-0.02836970933, 1.009995398, 0.02104144119],
+    [0.01231401486, -0.0205076493, 1.330365926],
+];
+
+const D50_WHITE: [f32; 3] = [0.3457 / 0.3585, 1.0, (1.0 - 0.3457 - 0.3585) / 0.3585];
+
+/// Chromaticity of the D65 white point
+const D65_WHITE_XY: [f32; 2] = [0.3127, 0.3290];
+
+const EPSILON: f32 = 216. / 24389.;
+const KAPPA: f32 = 24389. / 27.;
+
+/// CIE XYZ relative to the D50 white point
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct XyzD50a {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub alpha: f32,
+}
+
+impl XyzD50a {
+    pub fn new(x: f32, y: f32, z: f32, alpha: f32) -> Self {
+        Self { x, y, z, alpha }
+    }
+}
+
+impl From<Xyza> for XyzD50a {
+    fn from(xyza: Xyza) -> Self {
+        let [x, y, z] = mul(&D65_TO_D50, [xyza.x, xyza.y, xyza.z]);
+        XyzD50a::new(x, y, z, xyza.alpha)
+    }
+}
+
+impl From<XyzD50a> for Xyza {
+    fn from(xyz: XyzD50a) -> Self {
+        let [x, y, z] = mul(&D50_TO_D65, [xyz.x, xyz.y, xyz.z]);
+        Xyza::new(x, y, z, xyz.alpha)
+    }
+}
+
+impl From<LinearRgba> for XyzD50a {
+    fn from(rgba: LinearRgba) -> Self {
+        Xyza::from(rgba).into()
+    }
+}
+
+impl From<XyzD50a> for LinearRgba {
+    fn from(xyz: XyzD50a) -> Self {
+        Xyza::from(xyz).into()
+    }
+}
+
+/// CIE xyY relative to the D65 white point: chromaticity `x`, `y` and luminance `Y`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Xyya {
+    pub x: f32,
+    pub y: f32,
+    pub luminance: f32,
+    pub alpha: f32,
+}
+
+impl Xyya {
+    pub fn new(x: f32, y: f32, luminance: f32, alpha: f32) -> Self {
+        Self {
+            x,
+            y,
+            luminance,
+            alpha,
+        }
+    }
+}
+
+impl From<Xyza> for Xyya {
+    fn from(xyz: Xyza) -> Self {
+        let sum = xyz.x + xyz.y + xyz.z;
+        // Black has no chromaticity, use the white point like for grays
+        if sum == 0. {
+            let [x, y] = D65_WHITE_XY;
+            return Xyya::new(x, y, 0., xyz.alpha);
+        }
+        Xyya::new(xyz.x / sum, xyz.y / sum, xyz.y, xyz.alpha)
+    }
+}
+
+impl From<Xyya> for Xyza {
+    fn from(xyy: Xyya) -> Self {
+        if xyy.y == 0. {
+            return Xyza::new(0., 0., 0., xyy.alpha);
+        }
+        let scale = xyy.luminance / xyy.y;
+        Xyza::new(
+            xyy.x * scale,
+            xyy.luminance,
+            (1. - xyy.x - xyy.y) * scale,
+            xyy.alpha,
+        )
+    }
+}
+
+impl From<LinearRgba> for Xyya {
+    fn from(rgba: LinearRgba) -> Self {
+        Xyza::from(rgba).into()
+    }
+}
+
+impl From<Xyya> for LinearRgba {
+    fn from(xyy: Xyya) -> Self {
+        Xyza::from(xyy).into()
+    }
+}
+
+/// CIE L\*a\*b\* (D50) as used by CSS `lab()`. Lightness is in the range 0-100.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CieLaba {
+    pub lightness: f32,
+    pub a: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+impl CieLaba {
+    pub fn new(lightness: f32, a: f32, b: f32, alpha: f32) -> Self {
+        Self {
+            lightness,
+            a,
+            b,
+            alpha,
+        }
+    }
+}
+
+impl From<XyzD50a> for CieLaba {
+    fn from(xyz: XyzD50a) -> Self {
+        let f = |v: f32| {
+            if v > EPSILON {
+                v.cbrt()
+            } else {
+                (KAPPA * v + 16.) / 116.
+            }
+        };
+        let fx = f(xyz.x / D50_WHITE[0]);
+        let fy = f(xyz.y / D50_WHITE[1]);
+        let fz = f(xyz.z / D50_WHITE[2]);
+
+        CieLaba::new(
+            116. * fy - 16.,
+            500. * (fx - fy),
+            200. * (fy - fz),
+            xyz.alpha,
+        )
+    }
+}
+
+impl From<CieLaba> for XyzD50a {
+    fn from(lab: CieLaba) -> Self {
+        let fy = (lab.lightness + 16.) / 116.;
+        let fx = lab.a / 500. + fy;
+        let fz = fy - lab.b / 200.;
+
+        let f_inv = |f: f32| {
+            let f3 = f.powi(3);
+            if f3 > EPSILON {
+                f3
+            } else {
+                (116. * f - 16.) / KAPPA
+            }
+        };
+        let y = if lab.lightness > KAPPA * EPSILON {
+            fy.powi(3)
+        } else {
+            lab.lightness / KAPPA
+        };
+
+        XyzD50a::new(
+            f_inv(fx) * D50_WHITE[0],
+            y * D50_WHITE[1],
+            f_inv(fz) * D50_WHITE[2],
+            lab.alpha,
+        )
+    }
+}
+
+impl From<LinearRgba> for CieLaba {
+    fn from(rgba: LinearRgba) -> Self {
+        XyzD50a::from(rgba).into()
+    }
+}
+
+impl From<CieLaba> for LinearRgba {
+    fn from(lab: CieLaba) -> Self {
+        XyzD50a::from(lab).into()
+    }
+}
+
+/// Polar form of [`CieLaba`] as used by CSS `lch()`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CieLcha {
+    pub lightness: f32,
+    pub chroma: f32,
+    pub hue: f32,
+    pub alpha: f32,
+}
+
+impl CieLcha {
+    pub fn new(lightness: f32, chroma: f32, hue: f32, alpha: f32) -> Self {
+        Self {
+            lightness,
+            chroma,
+            hue,
+            alpha,
+        }
+    }
+}
+
+impl From<CieLaba> for CieLcha {
+    fn from(lab: CieLaba) -> Self {
+        let chroma = (lab.a * lab.a + lab.b * lab.b).sqrt();
+        let hue = lab.b.atan2(lab.a).to_degrees().rem_euclid(360.);
+        CieLcha::new(lab.lightness, chroma, hue, lab.alpha)
+    }
+}
+
+impl From<CieLcha> for CieLaba {
+    fn from(lch: CieLcha) -> Self {
+        let (sin, cos) = lch.hue.to_radians().sin_cos();
+        CieLaba::new(lch.lightness, lch.chroma * cos, lch.chroma * sin, lch.alpha)
+    }
+}
+
+impl From<LinearRgba> for CieLcha {
+    fn from(rgba: LinearRgba) -> Self {
+        CieLaba::from(rgba).into()
+    }
+}
+
+impl From<CieLcha> for LinearRgba {
+    fn from(lch: CieLcha) -> Self {
+        CieLaba::from(lch).into()
+    }
+}
+
+/// Naive device CMYK as used by CSS `device-cmyk()`, without an ICC profile. Channels are 0-1.
+///
+/// Converting from RGB uses full gray component replacement (GCR): black takes all of the
+/// darkness the three inks have in common, so at least one of cyan, magenta and yellow is
+/// zero and neutral grays are printed with black only. Values that came from elsewhere, with
+/// less black, still convert to RGB exactly as written.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Cmyka {
+    pub cyan: f32,
+    pub magenta: f32,
+    pub yellow: f32,
+    pub black: f32,
+    pub alpha: f32,
+}
+
+impl Cmyka {
+    pub fn new(cyan: f32, magenta: f32, yellow: f32, black: f32, alpha: f32) -> Self {
+        Self {
+            cyan,
+            magenta,
+            yellow,
+            black,
+            alpha,
+        }
+    }
+}
+
+impl From<Srgba> for Cmyka {
+    fn from(rgba: Srgba) -> Self {
+        let [r, g, b] = [rgba.red, rgba.green, rgba.blue].map(|c| c.clamp(0., 1.));
+        let black = 1. - r.max(g).max(b);
+        if black >= 1. {
+            return Cmyka::new(0., 0., 0., 1., rgba.alpha);
+        }
+        let ink = |c: f32| (1. - c - black) / (1. - black);
+        Cmyka::new(ink(r), ink(g), ink(b), black, rgba.alpha)
+    }
+}
+
+impl From<Cmyka> for Srgba {
+    fn from(cmyk: Cmyka) -> Self {
+        let rgb = |ink: f32| 1. - (ink * (1. - cmyk.black) + cmyk.black).min(1.);
+        Srgba::new(
+            rgb(cmyk.cyan),
+            rgb(cmyk.magenta),
+            rgb(cmyk.yellow),
+            cmyk.alpha,
+        )
+    }
+}
+
+impl From<LinearRgba> for Cmyka {
+    fn from(rgba: LinearRgba) -> Self {
+        Srgba::from(rgba).into()
+    }
+}
+
+impl From<Cmyka> for LinearRgba {
+    fn from(cmyk: Cmyka) -> Self {
+        Srgba::from(cmyk).into()
+    }
+}
+
+/// Predefined RGB and XYZ spaces of the CSS `color()` function
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, strum::Display, strum::EnumString, strum::IntoStaticStr,
+)]
+#[strum(serialize_all = "kebab-case")]
+pub enum CssColorSpace {
+    Srgb,
+    SrgbLinear,
+    DisplayP3,
+    A98Rgb,
+    ProphotoRgb,
+    Rec2020,
+    XyzD50,
+    #[strum(to_string = "xyz-d65", serialize = "xyz")]
+    XyzD65,
+}
+
+const LIN_P3_TO_XYZ: Mat3 = [
+    [0.4865709486, 0.2656676932, 0.1982172852],
+    [0.2289745641, 0.6917385218, 0.07928691409],
+    [0.0, 0.04511338186, 1.043944369],
+];
+
+const XYZ_TO_LIN_P3: Mat3 = [
+    [2.493496912, -0.9313836179, -0.4027107845],
+    [-0.8294889696, 1.76266406, 0.02362468584],
+    [0.03584583024, -0.07617238927, 0.956884524],
+];
+
+const LIN_A98_TO_XYZ: Mat3 = [
+    [0.5766690429, 0.1855582379, 0.1882286462],
+    [0.2973449753, 0.6273635663, 0.07529145849],
+    [0.02703136139, 0.07068885254, 0.9913375368],
+];
+
+const XYZ_TO_LIN_A98: Mat3 = [
+    [2.041587904, -0.5650069743, -0.3447313508],
+    [-0.9692436363, 1.875967502, 0.04155505741],
+    [0.01344428063, -0.1183623922, 1.015174994],
+];
+
+/// ProPhoto is relative to D50
+const LIN_PROPHOTO_TO_XYZ_D50: Mat3 = [
+    [0.7977666449, 0.1351812974, 0.03134773413],
+    [0.2880748288, 0.7118352342, 0.00008993693873],
+    [0.0, 0.0, 0.8251046025],
+];
+
+const XYZ_D50_TO_LIN_PROPHOTO: Mat3 = [
+    [1.345786882, -0.2555720874, -0.05110186498],
+    [-0.5446307051, 1.508247743, 0.02052744744],
+    [0.0, 0.0, 1.211967546],
+];
+
+const LIN_REC2020_TO_XYZ: Mat3 = [
+    [0.6369580483, 0.1446169036, 0.1688809752],
+    [0.262700212, 0.6779980715, 0.05930171647],
+    [0.0, 0.02807269305, 1.060985058],
+];
+
+const XYZ_TO_LIN_REC2020: Mat3 = [
+    [1.716651188, -0.3556707838, -0.2533662814],
+    [-0.6666843518, 1.616481237, 0.01576854581],
+    [0.01763985745, -0.04277061326, 0.9421031212],
+];
+
+/// Applies a transfer function symmetrically around zero like CSS does for extended ranges
+fn signed(v: f32, f: impl Fn(f32) -> f32) -> f32 {
+    f(v.abs()).copysign(v)
+}
+
+fn srgb_to_linear(v: f32) -> f32 {
+    signed(v, Srgba::gamma_function)
+}
+
+fn srgb_from_linear(v: f32) -> f32 {
+    signed(v, Srgba::gamma_function_inverse)
+}
+
+fn a98_to_linear(v: f32) -> f32 {
+    signed(v, |v| v.powf(563. / 256.))
+}
+
+fn a98_from_linear(v: f32) -> f32 {
+    signed(v, |v| v.powf(256. / 563.))
+}
+
+fn prophoto_to_linear(v: f32) -> f32 {
+    signed(v, |v| {
+        if v <= 16. / 512. {
+            v / 16.
+        } else {
+            v.powf(1.8)
+        }
+    })
+}
+
+fn prophoto_from_linear(v: f32) -> f32 {
+    signed(v, |v| {
+        if v >= 1. / 512. {
+            v.powf(1. / 1.8)
+        } else {
+            16. * v
+        }
+    })
+}
+
+const REC2020_ALPHA: f32 = 1.09929682680944;
+const REC2020_BETA: f32 = 0.018053968510807;
+
+fn rec2020_to_linear(v: f32) -> f32 {
+    signed(v, |v| {
+        if v < REC2020_BETA * 4.5 {
+            v / 4.5
+        } else {
+            ((v + REC2020_ALPHA - 1.) / REC2020_ALPHA).powf(1. / 0.45)
+        }
+    })
+}
+
+fn rec2020_from_linear(v: f32) -> f32 {
+    signed(v, |v| {
+        if v < REC2020_BETA {
+            v * 4.5
+        } else {
+            REC2020_ALPHA * v.powf(0.45) - (REC2020_ALPHA - 1.)
+        }
+    })
+}
+
+fn rgb_to_xyz(m: &Mat3, transfer: fn(f32) -> f32, [r, g, b]: [f32; 3]) -> [f32; 3] {
+    mul(m, [transfer(r), transfer(g), transfer(b)])
+}
+
+fn xyz_to_rgb(m: &Mat3, transfer: fn(f32) -> f32, xyz: [f32; 3]) -> [f32; 3] {
+    mul(m, xyz).map(transfer)
+}
+
+impl CssColorSpace {
+    pub fn to_linear_rgba(self, [c1, c2, c3, alpha]: [f32; 4]) -> LinearRgba {
+        let c = [c1, c2, c3];
+        let xyz = |[x, y, z]: [f32; 3]| LinearRgba::from(Xyza::new(x, y, z, alpha));
+        let xyz_d50 = |[x, y, z]: [f32; 3]| LinearRgba::from(XyzD50a::new(x, y, z, alpha));
+        match self {
+            Self::Srgb => Srgba::new(c1, c2, c3, alpha).into(),
+            Self::SrgbLinear => LinearRgba::new(c1, c2, c3, alpha),
+            Self::DisplayP3 => xyz(rgb_to_xyz(&LIN_P3_TO_XYZ, srgb_to_linear, c)),
+            Self::A98Rgb => xyz(rgb_to_xyz(&LIN_A98_TO_XYZ, a98_to_linear, c)),
+            Self::ProphotoRgb => {
+                xyz_d50(rgb_to_xyz(&LIN_PROPHOTO_TO_XYZ_D50, prophoto_to_linear, c))
+            }
+            Self::Rec2020 => xyz(rgb_to_xyz(&LIN_REC2020_TO_XYZ, rec2020_to_linear, c)),
+            Self::XyzD50 => xyz_d50(c),
+            Self::XyzD65 => xyz(c),
+        }
+    }
+
+    pub fn to_values(self, rgba: LinearRgba) -> [f32; 4] {
+        let xyz = || {
+            let c = Xyza::from(rgba);
+            [c.x, c.y, c.z]
+        };
+        let xyz_d50 = || {
+            let c = XyzD50a::from(rgba);
+            [c.x, c.y, c.z]
+        };
+        let [c1, c2, c3] = match self {
+            Self::Srgb => Srgba::from(rgba).to_f32_array_no_alpha(),
+            Self::SrgbLinear => rgba.to_f32_array_no_alpha(),
+            Self::DisplayP3 => xyz_to_rgb(&XYZ_TO_LIN_P3, srgb_from_linear, xyz()),
+            Self::A98Rgb => xyz_to_rgb(&XYZ_TO_LIN_A98, a98_from_linear, xyz()),
+            Self::ProphotoRgb => {
+                xyz_to_rgb(&XYZ_D50_TO_LIN_PROPHOTO, prophoto_from_linear, xyz_d50())
+            }
+            Self::Rec2020 => xyz_to_rgb(&XYZ_TO_LIN_REC2020, rec2020_from_linear, xyz()),
+            Self::XyzD50 => xyz_d50(),
+            Self::XyzD65 => xyz(),
+        };
+        [c1, c2, c3, rgba.alpha]
+    }
+}
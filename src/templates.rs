@@ -0,0 +1,464 @@
+//! User defined formats, read from a config file of templates with typed placeholders.
+//!
+//! Each line is `<name> [space] = <template>`, lines starting with `#` are comments.
+//! The space is one of the `color-mix()` interpolation spaces, e.g. `srgb-linear` or `oklch`,
+//! and defaults to `srgb`.
+//!
+//! Placeholders are written as `{channel:kind}` and literal braces as `{{` and `}}`.
+//! Channels are named by the space, e.g. `r g b`, `l c h` or `h s l`, plus `alpha`
+//! (or `a` when the space has no `a` channel). The kind sets the range of the value:
+//! - `f` or `f3`: the plain value, e.g. 0 to 1 for RGB, optionally with fixed decimals
+//! - `u8`: an integer from 0 to 255
+//! - `hex` or `HEX`: two hex digits
+//! - `pct` or `pct1`: a percentage without the `%` sign
+//! - `deg`, `rad` or `turn`: a hue angle without the unit
+//!
+//! ```text
+//! vec3 srgb-linear = vec3({r:f3}, {g:f3}, {b:f3})
+//! rgb8 = Color::rgb8({r:u8}, {g:u8}, {b:u8})
+//! object = {{r: {r:u8}, g: {g:u8}, b: {b:u8}}}
+//! oklch_deg oklch = oklch({l:pct}% {c:f3} {h:deg}deg)
+//! ```
+
+use std::{fmt, sync::OnceLock};
+
+use bevy_color::{Color, LinearRgba};
+use winnow::{
+    ModalResult, Parser,
+    ascii::{digit1, space0},
+    error::{StrContext, StrContextValue},
+    token::take_while,
+};
+
+use crate::{
+    formats::{ColorFormat, expected, number_parser, signed_num},
+    mix::MixSpace,
+    spaces::CssColorSpace,
+};
+
+static TEMPLATES: OnceLock<Vec<Template>> = OnceLock::new();
+
+/// The loaded templates, empty until [`load_templates`] has been called
+pub fn templates() -> &'static [Template] {
+    TEMPLATES.get().map_or(&[], Vec::as_slice)
+}
+
+/// Why the user formats couldn't be loaded. Nothing is loaded then.
+#[derive(Debug)]
+pub struct LoadTemplatesError {
+    pub message: String,
+    /// The file was given with `$OKLCH_COLOR_PICKER_FORMATS` instead of being the default one
+    // Not really dead but my lib system messes with compilation
+    #[allow(dead_code)]
+    pub explicit: bool,
+}
+
+impl fmt::Display for LoadTemplatesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Reads the templates from `$OKLCH_COLOR_PICKER_FORMATS`, or from `oklch-color-picker/formats.conf`
+/// in the config directory. A missing default file is not an error.
+/// Must be called before any formats are listed or parsed.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_templates() -> Result<(), LoadTemplatesError> {
+    use std::{env, fs, io, path::PathBuf};
+
+    let explicit_path = env::var_os("OKLCH_COLOR_PICKER_FORMATS");
+    let explicit = explicit_path.is_some();
+    let error = |message| LoadTemplatesError { message, explicit };
+    let path = match explicit_path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let config_dir = env::var_os("XDG_CONFIG_HOME")
+                .or_else(|| env::var_os("APPDATA"))
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+            let Some(config_dir) = config_dir else {
+                return Ok(());
+            };
+            config_dir.join("oklch-color-picker").join("formats.conf")
+        }
+    };
+
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit => return Ok(()),
+        Err(e) => return Err(error(format!("{}: {e}", path.display()))),
+    };
+    let templates = parse_templates(&text)
+        .map_err(|(line, e)| error(format!("{}:{line}: {e}", path.display())))?;
+    _ = TEMPLATES.set(templates);
+    Ok(())
+}
+
+/// Parses a config file, errors have the line number
+pub fn parse_templates(text: &str) -> Result<Vec<Template>, (usize, String)> {
+    let mut templates: Vec<Template> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let template = Template::from_definition(line).map_err(|e| (i + 1, e))?;
+        if ColorFormat::from_name(&template.name).is_some()
+            || templates.iter().any(|t| t.name == template.name)
+        {
+            return Err((i + 1, format!("format `{}` already exists", template.name)));
+        }
+        templates.push(template);
+    }
+    Ok(templates)
+}
+
+/// How a placeholder value is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Float,
+    Byte,
+    Hex,
+    UpperHex,
+    Percent,
+    Degrees,
+    Radians,
+    Turns,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Placeholder {
+        channel: usize,
+        kind: Kind,
+        decimals: Option<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub name: String,
+    space: MixSpace,
+    parts: Vec<Part>,
+}
+
+impl Template {
+    /// Parses a `<name> [space] = <template>` line
+    pub fn from_definition(line: &str) -> Result<Self, String> {
+        let (head, text) = line
+            .split_once('=')
+            .ok_or("expected `<name> [space] = <template>`")?;
+
+        let mut words = head.split_whitespace();
+        let name = words.next().ok_or("missing format name")?;
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-'))
+        {
+            return Err(format!(
+                "invalid format name `{name}`, use only letters, digits, `_` and `-`"
+            ));
+        }
+        let space = match words.next() {
+            Some(space) => {
+                MixSpace::from_name(space).ok_or_else(|| format!("unknown space `{space}`"))?
+            }
+            None => MixSpace::Rectangular(CssColorSpace::Srgb),
+        };
+        if let Some(word) = words.next() {
+            return Err(format!("expected `=` instead of `{word}`"));
+        }
+
+        let mut template = Self {
+            name: name.to_string(),
+            space,
+            parts: Vec::new(),
+        };
+        template.parse_parts(text.trim())?;
+        if !template
+            .parts
+            .iter()
+            .any(|p| matches!(p, Part::Placeholder { .. }))
+        {
+            return Err("the template has no placeholders".to_string());
+        }
+        Ok(template)
+    }
+
+    fn parse_parts(&mut self, template: &str) -> Result<(), String> {
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let (spec, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or("unclosed `{`, write `{{` for a literal brace")?;
+                    chars = rest.chars();
+                    if !literal.is_empty() {
+                        self.parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    self.parts.push(self.placeholder(spec)?);
+                }
+                '}' => return Err("unmatched `}`, write `}}` for a literal brace".to_string()),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            self.parts.push(Part::Literal(literal));
+        }
+        Ok(())
+    }
+
+    /// Parses `channel:kind` from inside the braces
+    fn placeholder(&self, spec: &str) -> Result<Part, String> {
+        let (channel_name, kind) = spec.split_once(':').unwrap_or((spec, "f"));
+        let names = self.channel_names();
+        let channel = match names.iter().position(|n| *n == channel_name) {
+            Some(i) => i,
+            None if channel_name == "alpha" || channel_name == "a" => 3,
+            None => {
+                return Err(format!(
+                    "unknown channel `{channel_name}`, expected one of {}, alpha",
+                    names.join(", ")
+                ));
+            }
+        };
+
+        let (kind, decimals) = match kind {
+            "u8" => (Kind::Byte, None),
+            "hex" => (Kind::Hex, None),
+            "HEX" => (Kind::UpperHex, None),
+            _ => {
+                let digits = kind.trim_start_matches(|c: char| c.is_ascii_alphabetic());
+                let decimals = match digits {
+                    "" => None,
+                    digits => Some(
+                        digits
+                            .parse::<u8>()
+                            .map_err(|_| format!("invalid decimals in `{spec}`"))?,
+                    ),
+                };
+                let kind = match &kind[..kind.len() - digits.len()] {
+                    "f" => Kind::Float,
+                    "pct" => Kind::Percent,
+                    "deg" => Kind::Degrees,
+                    "rad" => Kind::Radians,
+                    "turn" => Kind::Turns,
+                    _ => {
+                        return Err(format!(
+                            "unknown kind in `{spec}`, expected f, u8, hex, HEX, pct, deg, rad or turn"
+                        ));
+                    }
+                };
+                (kind, decimals)
+            }
+        };
+
+        if matches!(kind, Kind::Degrees | Kind::Radians | Kind::Turns)
+            && self.space.hue_index() != Some(channel)
+        {
+            return Err(format!("angles are only for hue channels, in `{spec}`"));
+        }
+        Ok(Part::Placeholder {
+            channel,
+            kind,
+            decimals,
+        })
+    }
+
+    fn channel_names(&self) -> [&'static str; 3] {
+        match self.space {
+            MixSpace::Rectangular(CssColorSpace::XyzD50 | CssColorSpace::XyzD65) => ["x", "y", "z"],
+            MixSpace::Rectangular(_) => ["r", "g", "b"],
+            MixSpace::Lab | MixSpace::Oklab => ["l", "a", "b"],
+            MixSpace::Lch | MixSpace::Oklch => ["l", "c", "h"],
+            MixSpace::Hsl => ["h", "s", "l"],
+            MixSpace::Hwb => ["h", "w", "b"],
+        }
+    }
+
+    fn channel_name(&self, channel: usize) -> &'static str {
+        self.channel_names()
+            .get(channel)
+            .copied()
+            .unwrap_or("alpha")
+    }
+
+    /// The value that is written as 100% or 255
+    fn channel_max(&self, channel: usize) -> f32 {
+        if self.space.hue_index() == Some(channel) {
+            return 360.;
+        }
+        match (self.space, channel) {
+            (_, 3) => 1.,
+            (MixSpace::Lab | MixSpace::Lch, 0) => 100.,
+            (MixSpace::Lab, _) => 125.,
+            (MixSpace::Lch, _) => 150.,
+            (MixSpace::Oklab | MixSpace::Oklch, 1 | 2) => 0.4,
+            _ => 1.,
+        }
+    }
+
+    fn has_alpha(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|p| matches!(p, Part::Placeholder { channel: 3, .. }))
+    }
+
+    pub fn format(&self, color: LinearRgba) -> String {
+        let values = self.space.to_values(color);
+        let mut out = String::new();
+        for part in &self.parts {
+            let (channel, kind, decimals) = match part {
+                Part::Literal(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Part::Placeholder {
+                    channel,
+                    kind,
+                    decimals,
+                } => (*channel, *kind, *decimals),
+            };
+            let value = values[channel];
+            let max = self.channel_max(channel);
+            let byte = (value / max * 255.).round().clamp(0., 255.) as u8;
+            let number = |value: f32, default: i32| match decimals {
+                Some(d) => format!("{:.*}", d as usize, signed_num(value, d as i32)),
+                None => signed_num(value, default).to_string(),
+            };
+
+            let text = match kind {
+                // Always with a decimal point, so that it's a float literal in code
+                Kind::Float if decimals.is_none() => format!("{:?}", signed_num(value, 4)),
+                Kind::Float => number(value, 4),
+                Kind::Byte => byte.to_string(),
+                Kind::Hex => format!("{byte:02x}"),
+                Kind::UpperHex => format!("{byte:02X}"),
+                Kind::Percent => number(value / max * 100., 1),
+                Kind::Degrees => number(value, 1),
+                Kind::Radians => number(value.to_radians(), 4),
+                Kind::Turns => number(value / 360., 4),
+            };
+            out.push_str(&text);
+        }
+        out
+    }
+
+    pub fn color_parser(&self, input: &mut &str) -> ModalResult<(Color, bool)> {
+        let mut values = [0., 0., 0., 1.];
+        for part in &self.parts {
+            let (channel, kind) = match part {
+                Part::Literal(text) => {
+                    for c in text.chars() {
+                        if c.is_whitespace() {
+                            space0.parse_next(input)?;
+                        } else {
+                            c.context(StrContext::Expected(StrContextValue::CharLiteral(c)))
+                                .parse_next(input)?;
+                        }
+                    }
+                    continue;
+                }
+                Part::Placeholder { channel, kind, .. } => (*channel, *kind),
+            };
+            let max = self.channel_max(channel);
+            let label = StrContext::Label(self.channel_name(channel));
+
+            let value = match kind {
+                Kind::Byte => digit1
+                    .try_map(str::parse::<u8>)
+                    .context(expected("integer from 0 to 255"))
+                    .context(label)
+                    .parse_next(input)? as f32,
+                Kind::Hex | Kind::UpperHex => take_while(2, |c: char| c.is_ascii_hexdigit())
+                    .try_map(|s| u8::from_str_radix(s, 16))
+                    .context(expected("two hex digits"))
+                    .context(label)
+                    .parse_next(input)? as f32,
+                _ => number_parser.context(label).parse_next(input)?,
+            };
+            values[channel] = match kind {
+                Kind::Float | Kind::Degrees => value,
+                Kind::Byte | Kind::Hex | Kind::UpperHex => value / 255. * max,
+                Kind::Percent => value / 100. * max,
+                Kind::Radians => value.to_degrees(),
+                Kind::Turns => value * 360.,
+            };
+        }
+        Ok((self.space.to_color(values).into(), self.has_alpha()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_color::{ColorToPacked, Oklcha, Srgba};
+
+    use super::*;
+
+    fn template_parse(template: &Template, s: &str) -> Option<(Color, bool)> {
+        (|i: &mut &str| template.color_parser(i)).parse(s).ok()
+    }
+
+    #[test]
+    fn template_linear() {
+        let template =
+            Template::from_definition("vec3 srgb-linear = vec3({r:f3}, {g:f3}, {b})").unwrap();
+        let color = LinearRgba::rgb(0.25, 0.5, 1.);
+        assert_eq!(template.format(color), "vec3(0.250, 0.500, 1.0)");
+        assert_eq!(
+            template_parse(&template, "vec3(0.25,0.5,  1e0)"),
+            Some((color.into(), false))
+        );
+        assert_eq!(template_parse(&template, "vec4(0.25, 0.5, 1)"), None);
+    }
+
+    #[test]
+    fn template_ranges() {
+        let template =
+            Template::from_definition("object = {{r: {r:u8}, g: {g:hex}, b: {b:pct}, a: {a:f}}}")
+                .unwrap();
+        let color = Srgba::rgba_u8(255, 0, 51, 127);
+        assert_eq!(
+            template.format(color.into()),
+            "{r: 255, g: 00, b: 20, a: 0.498}"
+        );
+        let (parsed, use_alpha) =
+            template_parse(&template, "{r: 255, g: 00, b: 20, a: 0.498}").unwrap();
+        assert!(use_alpha);
+        assert_eq!(Srgba::from(parsed).to_u8_array(), color.to_u8_array());
+
+        let template =
+            Template::from_definition("oklch_deg oklch = {l:pct}% {c:f3} {h:deg}deg").unwrap();
+        let color = Oklcha::new(0.5, 0.1, 120., 1.);
+        assert_eq!(template.format(color.into()), "50% 0.100 120deg");
+        let (parsed, _) = template_parse(&template, "50% 0.1 120deg").unwrap();
+        let parsed = Oklcha::from(parsed);
+        assert!((parsed.hue - 120.).abs() < 0.01 && (parsed.chroma - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn template_definition_errors() {
+        assert!(Template::from_definition("x = ({q}, {g}, {b})").is_err());
+        assert!(Template::from_definition("x = ({r:deg}, {g}, {b})").is_err());
+        assert!(Template::from_definition("x = ({r}, {g}, {b}").is_ok());
+        assert!(Template::from_definition("x = ({r}, {g}, {b})}").is_err());
+        assert!(Template::from_definition("x nope = ({r}, {g}, {b})").is_err());
+        assert!(Template::from_definition("x = plain text").is_err());
+        assert_eq!(
+            parse_templates("# comment\n\nhex = {r:hex}").map_err(|(line, _)| line),
+            Err(3)
+        );
+    }
+}